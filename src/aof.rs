@@ -0,0 +1,664 @@
+// src/aof.rs
+
+use crate::cache::EntrySnapshot;
+use crate::config::{AofSettings, FsyncPolicy, SnapshotSettings};
+use crate::namespace::{Namespaces, SharedNamespaces};
+use crate::snapshot;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/// 日志中的一条记录。
+/// 记录的是写入之后条目的完整状态而不是操作本身，重放时按版本号取最新的状态，
+/// 因此并发写入的记录即使在日志中乱序也不影响结果。
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Record {
+    Set {
+        ns: String,
+        // 记录时间（Unix 毫秒），重放时据此扣除流逝的 TTL
+        at_ms: u64,
+        #[serde(flatten)]
+        entry: EntrySnapshot,
+    },
+    // 删除版本号为 `version` 及更早的条目
+    Delete {
+        ns: String,
+        key: String,
+        version: u64,
+        // 记录时间（Unix 毫秒），重写日志时据此判断是否还需要保留
+        #[serde(default)]
+        at_ms: u64,
+    },
+    // 版本号高水位：重写日志时写在开头，重放后发出的版本号不小于 `next`
+    Version { ns: String, next: u64 },
+    // 一个事务的全部写入，写在同一行中，崩溃时不会只留下一部分
+    Batch { records: Vec<Record> },
+}
+
+/// 事务中需要记入日志的一次写入
+#[derive(Debug)]
+pub enum BatchOp {
+    Set(EntrySnapshot),
+    // 被删除条目的版本号
+    Delete { key: String, version: u64 },
+}
+
+enum Command {
+    Append(Box<Record>, Option<oneshot::Sender<()>>),
+    Compact(oneshot::Sender<Result<u64, String>>),
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 一个命名空间写入日志的句柄，由 `CacheStore` 在每次写入后调用
+#[derive(Debug, Clone)]
+pub struct MutationLog {
+    namespace: String,
+    fsync: FsyncPolicy,
+    tx: Sender<Command>,
+}
+
+impl MutationLog {
+    /// 记录一次写入。fsync 策略为 always 时返回的接收端在记录落盘后完成。
+    pub fn append_set(&self, entry: EntrySnapshot) -> Option<oneshot::Receiver<()>> {
+        self.append(Record::Set {
+            ns: self.namespace.clone(),
+            at_ms: now_ms(),
+            entry,
+        })
+    }
+
+    /// 记录一次删除，包括容量驱逐
+    pub fn append_delete(&self, key: &str, version: u64) -> Option<oneshot::Receiver<()>> {
+        self.append(Record::Delete {
+            ns: self.namespace.clone(),
+            key: key.to_string(),
+            version,
+            at_ms: now_ms(),
+        })
+    }
+
+    /// 把一个事务的全部写入记为一条记录，重放时要么全部生效，要么全部不生效
+    pub fn append_batch(&self, ops: Vec<BatchOp>) -> Option<oneshot::Receiver<()>> {
+        let at_ms = now_ms();
+        let records = ops
+            .into_iter()
+            .map(|op| match op {
+                BatchOp::Set(entry) => Record::Set { ns: self.namespace.clone(), at_ms, entry },
+                BatchOp::Delete { key, version } => Record::Delete {
+                    ns: self.namespace.clone(),
+                    key,
+                    version,
+                    at_ms,
+                },
+            })
+            .collect();
+        self.append(Record::Batch { records })
+    }
+
+    fn append(&self, record: Record) -> Option<oneshot::Receiver<()>> {
+        let (ack, done) = match self.fsync {
+            FsyncPolicy::Always => {
+                let (tx, rx) = oneshot::channel();
+                (Some(tx), Some(rx))
+            }
+            _ => (None, None),
+        };
+        if self.tx.send(Command::Append(Box::new(record), ack)).is_err() {
+            error!("Append-only log writer has stopped, mutation not logged");
+            return None;
+        }
+        done
+    }
+}
+
+/// 追加日志的写入端。
+/// 写入在独立的线程中进行，请求处理只需把记录放进队列。
+#[derive(Clone)]
+pub struct Aof {
+    settings: AofSettings,
+    tx: Sender<Command>,
+}
+
+impl Aof {
+    /// 打开日志并启动写入线程，随后把日志挂到所有命名空间上。
+    /// 重写日志时要保留快照中可能还存在的键的删除记录，因此需要快照目录。
+    pub fn start(
+        settings: &AofSettings,
+        snapshot: &SnapshotSettings,
+        namespaces: SharedNamespaces,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&settings.path)?;
+        let size = file.metadata()?.len();
+
+        let (tx, rx) = mpsc::channel();
+        let writer = Writer {
+            path: PathBuf::from(&settings.path),
+            snapshot_dir: PathBuf::from(&snapshot.dir),
+            fsync: settings.fsync,
+            compact_min_bytes: settings.compact_min_bytes,
+            file: BufWriter::new(file),
+            size,
+            base_size: size,
+            dirty: false,
+            namespaces: namespaces.clone(),
+        };
+        std::thread::Builder::new()
+            .name("aof-writer".to_string())
+            .spawn(move || writer.run(rx))?;
+
+        let aof = Self { settings: settings.clone(), tx };
+        for (name, cache) in namespaces.iter() {
+            cache.attach_log(aof.handle(name));
+        }
+        info!("Append-only log opened at {} ({} bytes)", settings.path, size);
+        Ok(aof)
+    }
+
+    fn handle(&self, namespace: &str) -> MutationLog {
+        MutationLog {
+            namespace: namespace.to_string(),
+            fsync: self.settings.fsync,
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// 立即重写日志，返回重写后的大小（字节）
+    pub async fn compact(&self) -> io::Result<u64> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .send(Command::Compact(tx))
+            .map_err(|_| io::Error::other("append-only log writer has stopped"))?;
+        rx.await
+            .map_err(|_| io::Error::other("append-only log writer has stopped"))?
+            .map_err(io::Error::other)
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    snapshot_dir: PathBuf,
+    fsync: FsyncPolicy,
+    compact_min_bytes: u64,
+    file: BufWriter<File>,
+    size: u64,
+    // 上次重写后的大小，日志增长到它的两倍时自动重写
+    base_size: u64,
+    // 有尚未 fsync 的写入
+    dirty: bool,
+    namespaces: SharedNamespaces,
+}
+
+impl Writer {
+    fn run(mut self, rx: Receiver<Command>) {
+        let mut last_sync = Instant::now();
+        loop {
+            let command = match rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // 一次取出队列中的全部记录，always 策略下合并为一次 fsync
+            let mut acks = Vec::new();
+            let mut compactions = Vec::new();
+            for command in command.into_iter().chain(rx.try_iter()) {
+                match command {
+                    Command::Append(record, ack) => {
+                        if let Err(e) = self.write(&record) {
+                            error!("Failed to append to log: {}", e);
+                        }
+                        acks.extend(ack);
+                    }
+                    Command::Compact(reply) => compactions.push(reply),
+                }
+            }
+
+            if let Err(e) = self.file.flush() {
+                error!("Failed to flush log: {}", e);
+            }
+            let sync_now = match self.fsync {
+                FsyncPolicy::Always => !acks.is_empty(),
+                FsyncPolicy::EverySecond => last_sync.elapsed() >= Duration::from_secs(1),
+                FsyncPolicy::Never => false,
+            };
+            if sync_now && self.dirty {
+                match self.file.get_ref().sync_data() {
+                    Ok(()) => self.dirty = false,
+                    Err(e) => error!("Failed to fsync log: {}", e),
+                }
+                last_sync = Instant::now();
+            }
+            for ack in acks {
+                let _ = ack.send(());
+            }
+
+            let auto_compact = self.size >= self.compact_min_bytes.max(self.base_size * 2);
+            if auto_compact || !compactions.is_empty() {
+                let result = self.compact().map_err(|e| e.to_string());
+                match &result {
+                    Ok(size) => info!("Append-only log compacted to {} bytes", size),
+                    Err(e) => error!("Failed to compact log: {}", e),
+                }
+                for reply in compactions {
+                    let _ = reply.send(result.clone());
+                }
+                if result.is_err() {
+                    // 避免每次写入都重试，等日志再增长一倍
+                    self.base_size = self.size;
+                }
+            }
+        }
+        let _ = self.file.flush();
+        let _ = self.file.get_ref().sync_data();
+    }
+
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.size += line.len() as u64;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// 用当前所有条目重写日志。
+    /// 重写期间的写入留在队列中，之后追加到新文件末尾；
+    /// 入队之前写入就已生效，所以导出的状态不会比旧日志中的记录更旧。
+    ///
+    /// 只写存活的条目还不够：每个命名空间先写版本号的高水位，重启后不会重新发出用过的版本号
+    /// （旧的 ETag 和锁令牌因此不会意外匹配）；最新快照之后删除的键保留删除记录，
+    /// 否则从快照恢复的旧值不会再被删除。
+    fn compact(&mut self) -> io::Result<u64> {
+        self.file.flush()?;
+        // 持有快照锁，确保判断删除记录时看到的是最新的快照
+        snapshot::while_no_snapshot(|| self.rewrite())
+    }
+
+    fn rewrite(&mut self) -> io::Result<u64> {
+        let snapshot_at = snapshot::latest_created_at(&self.snapshot_dir)?;
+        let latest = match snapshot_at {
+            Some(_) => reduce(read_records(&self.path)?).keys,
+            None => HashMap::new(),
+        };
+
+        let tmp_path = self.path.with_extension("rewrite");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        let mut size = 0;
+        let mut write = |record: &Record| -> io::Result<()> {
+            let mut line = serde_json::to_vec(record)?;
+            line.push(b'\n');
+            tmp.write_all(&line)?;
+            size += line.len() as u64;
+            Ok(())
+        };
+
+        let at_ms = now_ms();
+        let mut live = HashSet::new();
+        for (name, cache) in self.namespaces.iter() {
+            write(&Record::Version { ns: name.clone(), next: cache.version_high_water() })?;
+            for entry in cache.export() {
+                live.insert((name.clone(), entry.key.clone()));
+                write(&Record::Set { ns: name.clone(), at_ms, entry })?;
+            }
+        }
+        // 早于最新快照的删除不需要保留：快照中本来就没有这些键
+        for ((ns, key), state) in latest {
+            if state.entry.is_none()
+                && snapshot_at.is_some_and(|snapshot_at| state.deleted_at_ms >= snapshot_at)
+                && !live.contains(&(ns.clone(), key.clone()))
+            {
+                write(&Record::Delete { ns, key, version: state.version, at_ms: state.deleted_at_ms })?;
+            }
+        }
+        tmp.flush()?;
+        tmp.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.size = size;
+        self.base_size = size;
+        self.dirty = false;
+        Ok(size)
+    }
+}
+
+/// 重放时一个键在日志中的最新状态
+#[derive(Default)]
+struct LatestState {
+    version: u64,
+    // 最新的条目及其记录时间，`None` 表示已删除
+    entry: Option<(u64, EntrySnapshot)>,
+    // 已删除时删除记录的时间
+    deleted_at_ms: u64,
+}
+
+/// 日志中每个命名空间的版本号高水位，以及每个键的最新状态
+#[derive(Default)]
+struct LogState {
+    high_water: HashMap<String, u64>,
+    keys: HashMap<(String, String), LatestState>,
+}
+
+/// 按版本号把日志归并为每个键的最新状态
+fn reduce(records: Vec<Record>) -> LogState {
+    let mut state = LogState::default();
+    reduce_into(&mut state, records);
+    state
+}
+
+fn reduce_into(state: &mut LogState, records: Vec<Record>) {
+    for record in records {
+        match record {
+            Record::Set { ns, at_ms, entry } => {
+                let latest = state.keys.entry((ns, entry.key.clone())).or_default();
+                // 只改过期设置的写入沿用原来的版本号，同版本号的记录后写的生效，
+                // 但不会复活同版本号已删除的条目
+                let newer = entry.version > latest.version
+                    || (entry.version == latest.version && latest.entry.is_some());
+                if newer {
+                    latest.version = entry.version;
+                    latest.entry = Some((at_ms, entry));
+                }
+            }
+            Record::Delete { ns, key, version, at_ms } => {
+                let latest = state.keys.entry((ns, key)).or_default();
+                // 版本号相同时删除优先：删除记录的版本号就是被删除条目的版本号
+                if version >= latest.version {
+                    latest.version = version;
+                    latest.entry = None;
+                    latest.deleted_at_ms = at_ms;
+                }
+            }
+            Record::Version { ns, next } => {
+                let high_water = state.high_water.entry(ns).or_default();
+                *high_water = (*high_water).max(next);
+            }
+            Record::Batch { records } => reduce_into(state, records),
+        }
+    }
+}
+
+/// 重放日志，把每个键恢复到日志中最新的状态，返回恢复的条目数。
+/// 崩溃时写了一半的最后一行会被截掉，以免之后的追加接在它后面。
+pub async fn replay(settings: &AofSettings, namespaces: &Namespaces) -> io::Result<usize> {
+    let path = PathBuf::from(&settings.path);
+    let records = tokio::task::spawn_blocking(move || read_records(&path)).await??;
+    let LogState { high_water, keys: latest } = reduce(records);
+
+    for (ns, next) in high_water {
+        if let Ok(cache) = namespaces.get(&ns) {
+            cache.advance_version(next);
+        }
+    }
+
+    let now = now_ms();
+    let mut restored = 0;
+    let mut skipped_namespaces = Vec::new();
+    for ((ns, key), state) in latest {
+        let cache = match namespaces.get(&ns) {
+            Ok(cache) => cache,
+            Err(_) => {
+                if !skipped_namespaces.contains(&ns) {
+                    warn!("Skipping log records of unknown namespace '{}'", ns);
+                    skipped_namespaces.push(ns);
+                }
+                continue;
+            }
+        };
+        match state.entry {
+            Some((at_ms, entry)) => {
+                let elapsed = Duration::from_millis(now.saturating_sub(at_ms));
+                restored += cache.import(vec![entry], elapsed).await;
+            }
+            None => cache.delete_version(&key, state.version).await,
+        }
+    }
+
+    info!("Replayed append-only log {}: {} entries restored", settings.path, restored);
+    Ok(restored)
+}
+
+fn read_records(path: &PathBuf) -> io::Result<Vec<Record>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut records = Vec::new();
+    let mut line = Vec::new();
+    let mut valid_len = 0;
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        if line.last() != Some(&b'\n') {
+            warn!("Truncating incomplete record at the end of {}", path.display());
+            break;
+        }
+        match serde_json::from_slice(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping corrupt log record at byte {}: {}", valid_len, e),
+        }
+        valid_len += read as u64;
+    }
+
+    if valid_len < fs::metadata(path)?.len() {
+        OpenOptions::new().write(true).open(path)?.set_len(valid_len)?;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheItemTTL, CacheValue, TtlMode, TxnOp};
+    use crate::error::CacheError;
+    use crate::config::{CacheSettings, EvictionPolicy, Settings};
+    use serde_json::{Number, json};
+    use std::sync::Arc;
+
+    fn test_settings(name: &str) -> Settings {
+        let path = std::env::temp_dir().join(format!("my-cache-{}-{}.aof", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut settings = Settings::for_tests(CacheSettings {
+            capacity: 2,
+            eviction_policy: EvictionPolicy::Fifo,
+            ..CacheSettings::for_tests()
+        });
+        settings.aof = AofSettings {
+            enabled: true,
+            path: path.to_string_lossy().into_owned(),
+            fsync: FsyncPolicy::Always,
+            compact_min_bytes: 64 * 1024 * 1024,
+        };
+        settings
+    }
+
+    async fn restart(settings: &Settings) -> Arc<Namespaces> {
+        let namespaces = Arc::new(Namespaces::new(settings));
+        replay(&settings.aof, &namespaces).await.unwrap();
+        namespaces
+    }
+
+    #[tokio::test]
+    async fn test_replay_restores_latest_state() {
+        let settings = test_settings("replay");
+        let namespaces = restart(&settings).await;
+        Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&namespaces)).unwrap();
+        let cache = namespaces.get("").unwrap();
+
+        cache.set("a".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.increment("a".to_string(), Number::from(5), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        cache.set("b".to_string(), json!(2), CacheItemTTL::Custom(Duration::from_secs(60)), vec![]).await.unwrap();
+        cache.delete("b").await.unwrap();
+        cache.set("c".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        // 容量为 2 的 FIFO：写入 d 会驱逐 a，驱逐同样要记入日志
+        let version = cache.set("d".to_string(), json!(4), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        // 只改过期设置时版本号不变，重放时仍以后写的记录为准
+        cache.expire("c", CacheItemTTL::Custom(Duration::from_secs(60))).await.unwrap();
+
+        let restored = restart(&settings).await;
+        let cache = restored.get("").unwrap();
+        assert!(cache.get("a").await.is_none());
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(cache.get("c").await, Some(json!(3)));
+        assert_eq!(cache.ttl("c").await.unwrap().0, TtlMode::Fixed);
+        assert_eq!(cache.get_versioned("d").await, Some((json!(4).into(), version)));
+
+        let _ = fs::remove_file(&settings.aof.path);
+    }
+
+    #[tokio::test]
+    async fn test_lock_tokens_increase_across_restarts() {
+        let settings = test_settings("lock");
+        let namespaces = restart(&settings).await;
+        Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&namespaces)).unwrap();
+        let cache = namespaces.get("").unwrap();
+
+        let lease = Duration::from_millis(20);
+        cache.lock_acquire("job".to_string(), "a".to_string(), lease).await.unwrap();
+        let released = cache.lock_acquire("other".to_string(), "a".to_string(), lease).await.unwrap();
+        cache.lock_release("other", released).await.unwrap();
+        let expired = cache.lock_acquire("job2".to_string(), "a".to_string(), lease).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        // 过期和释放的锁都不会恢复，但之后的令牌仍然更大
+        let restored = restart(&settings).await;
+        let cache = restored.get("").unwrap();
+        let token = cache.lock_acquire("job".to_string(), "b".to_string(), lease).await.unwrap();
+        assert!(token > expired.max(released));
+
+        // 重写日志之后只剩下存活的锁，令牌也不会回退
+        let aof = Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&restored)).unwrap();
+        cache.lock_release("job", token).await.unwrap();
+        aof.compact().await.unwrap();
+        let restored = restart(&settings).await;
+        let cache = restored.get("").unwrap();
+        let next = cache.lock_acquire("job".to_string(), "c".to_string(), lease).await.unwrap();
+        assert!(next > token);
+
+        let _ = fs::remove_file(&settings.aof.path);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_keeps_deletes_after_snapshot() {
+        let mut settings = test_settings("tombstone");
+        let snapshot_dir = std::env::temp_dir().join(format!("my-cache-tombstone-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&snapshot_dir);
+        settings.snapshot.dir = snapshot_dir.to_string_lossy().into_owned();
+
+        let namespaces = restart(&settings).await;
+        let aof = Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&namespaces)).unwrap();
+        let cache = namespaces.get("").unwrap();
+
+        cache.set("old".to_string(), json!(0), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.delete("old").await.unwrap();
+        cache.set("k".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        // 时间精确到毫秒，让删除明确早于快照
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        snapshot::take_snapshot(&settings.snapshot, &namespaces).await.unwrap();
+
+        // 快照之后删除 k，重写日志后删除记录仍要保留；快照之前的删除可以丢弃
+        cache.delete("k").await.unwrap();
+        let last = cache.set("tmp".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.delete("tmp").await.unwrap();
+        aof.compact().await.unwrap();
+        let content = fs::read_to_string(&settings.aof.path).unwrap();
+        assert!(content.contains(r#""key":"k""#));
+        assert!(!content.contains(r#""key":"old""#));
+
+        // 与启动时相同：先恢复快照，再重放日志
+        let restored = Arc::new(Namespaces::new(&settings));
+        snapshot::restore_latest(&settings.snapshot, &restored).await.unwrap().unwrap();
+        replay(&settings.aof, &restored).await.unwrap();
+        let cache = restored.get("").unwrap();
+        assert!(cache.get("k").await.is_none());
+        // 版本号不会回退，旧的 ETag 不会匹配重新创建的键
+        let version = cache.set("tmp".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(version > last);
+
+        let _ = fs::remove_file(&settings.aof.path);
+        let _ = fs::remove_dir_all(&snapshot_dir);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_logged_as_one_record() {
+        let mut settings = test_settings("txn");
+        settings.cache.eviction_policy = EvictionPolicy::None;
+        let namespaces = restart(&settings).await;
+        Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&namespaces)).unwrap();
+        let cache = namespaces.get("").unwrap();
+        let set = |key: &str, value: i64| TxnOp::Set {
+            key: key.to_string(),
+            value: CacheValue::Json(json!(value)),
+            ttl: CacheItemTTL::Permanent.into(),
+            tags: vec![],
+        };
+
+        cache.set("{u}:a".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.transaction(vec![set("{u}:a", 2), set("{u}:b", 3)]).await.unwrap();
+        // 缓存已满，写入新键 c 失败，之前对 a 的写入撤销，也不会记入日志
+        let failed = vec![set("{u}:a", 4), set("{u}:c", 5)];
+        assert!(matches!(cache.transaction(failed).await, Err(CacheError::CacheFull)));
+
+        let content = fs::read_to_string(&settings.aof.path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.lines().nth(1).unwrap().starts_with(r#"{"op":"batch""#));
+
+        let restored = restart(&settings).await;
+        let cache = restored.get("").unwrap();
+        assert_eq!(cache.get("{u}:a").await, Some(json!(2)));
+        assert_eq!(cache.get("{u}:b").await, Some(json!(3)));
+        assert!(cache.get("{u}:c").await.is_none());
+
+        let _ = fs::remove_file(&settings.aof.path);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_and_truncated_tail() {
+        let settings = test_settings("compact");
+        let namespaces = restart(&settings).await;
+        let aof = Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&namespaces)).unwrap();
+        let cache = namespaces.get("").unwrap();
+
+        for i in 0..20 {
+            cache.set("k".to_string(), json!(i), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        }
+        let before = fs::metadata(&settings.aof.path).unwrap().len();
+        let after = aof.compact().await.unwrap();
+        assert!(after < before);
+        cache.set("k2".to_string(), json!("x"), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        // 模拟崩溃时写了一半的记录
+        let mut file = OpenOptions::new().append(true).open(&settings.aof.path).unwrap();
+        file.write_all(br#"{"op":"set","ns":"default","#).unwrap();
+        drop(file);
+
+        let restored = restart(&settings).await;
+        let cache = restored.get("").unwrap();
+        assert_eq!(cache.get("k").await, Some(json!(19)));
+        assert_eq!(cache.get("k2").await, Some(json!("x")));
+        let content = fs::read_to_string(&settings.aof.path).unwrap();
+        assert!(content.ends_with('\n'));
+
+        let _ = fs::remove_file(&settings.aof.path);
+    }
+}
@@ -1,22 +1,478 @@
 // src/cache.rs
 
+use crate::aof::{BatchOp, MutationLog};
+use crate::cluster::hash_slot;
+use crate::config::{CacheSettings, EvictionPolicy};
+use crate::error::CacheError;
+use crate::origin::{Origin, Origins};
+use crate::sorted_set::SortedSet;
+use crate::write_behind::WriteBehindQueue;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bytes::Bytes;
+use log::warn;
+use moka::Expiry;
 use moka::future::Cache;
-use serde_json::Value;
-use std::hash::Hash;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use moka::notification::RemovalCause;
+use moka::ops::compute::{CompResult, Op};
+use moka::policy::EvictionPolicy as MokaEvictionPolicy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{OnceCell, RwLock, broadcast, mpsc};
 
 #[derive(Debug, Clone, Copy)]
 pub enum CacheItemTTL{
     Default,
     Permanent,
     Custom(Duration),
+    // 滑动过期：超过给定时长未被读取才过期，每次读取都会重新计时
+    Idle(Duration),
+}
+
+/// 新建条目时的过期设置，以及是否给 Default / Custom 的 TTL 加抖动
+#[derive(Debug, Clone, Copy)]
+pub struct EntryTtl {
+    pub ttl: CacheItemTTL,
+    pub jitter: bool,
+}
+
+impl From<CacheItemTTL> for EntryTtl {
+    fn from(ttl: CacheItemTTL) -> Self {
+        Self { ttl, jitter: true }
+    }
+}
+
+/// 写入时给 TTL 加的随机延长，避免同一批写入的键同时过期
+#[derive(Debug, Clone, Copy)]
+struct TtlJitter {
+    percent: u32,
+    absolute: Duration,
+}
+
+impl TtlJitter {
+    fn new(settings: &CacheSettings) -> Self {
+        Self {
+            percent: settings.ttl_jitter_percent.unwrap_or(0),
+            absolute: Duration::from_millis(settings.ttl_jitter_ms.unwrap_or(0)),
+        }
+    }
+
+    /// 在 [ttl, ttl + 抖动范围] 中随机取值，键至少存活请求的 TTL
+    fn apply(&self, ttl: Duration) -> Duration {
+        // TTL 很大时按百分比计算可能溢出，饱和到最大值
+        let spread = ttl
+            .checked_mul(self.percent)
+            .map_or(Duration::MAX, |d| d / 100)
+            .max(self.absolute);
+        if spread.is_zero() {
+            return ttl;
+        }
+        ttl.saturating_add(rand::thread_rng().gen_range(Duration::ZERO..=spread))
+    }
+}
+
+/// 哈希、列表、集合和有序集合最多包含的元素个数
+pub const MAX_COLLECTION_LEN: usize = 10_000;
+
+/// 列表的一端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListEnd {
+    Front,
+    Back,
+}
+
+/// 有序集合的读取范围，均为闭区间
+#[derive(Debug, Clone, Copy)]
+pub enum ZsetRange {
+    // 按排名，负数排名从末尾算起
+    Rank { start: i64, stop: i64 },
+    Score { min: f64, max: f64 },
+}
+
+/// 条目过期之后的宽限期，只用于配置了回源的键
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Grace {
+    // 过期后这段时间内直接返回旧值，同时在后台刷新
+    pub revalidate: Duration,
+    // 过期后这段时间内源不可用时仍返回旧值
+    pub if_error: Duration,
+}
+
+impl Grace {
+    /// 条目过期后还需在缓存中保留多久
+    fn retain(&self) -> Duration {
+        self.revalidate.max(self.if_error)
+    }
+}
+
+/// 读取结果的新鲜度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Fresh,
+    // 已过期但在 stale-while-revalidate 宽限期内，后台正在刷新
+    Stale,
+    // 已过期且刷新失败，按 stale-if-error 返回旧值
+    StaleIfError,
+}
+
+tokio::task_local! {
+    // 为真时读取不算一次访问：不延长滑动过期条目的寿命，用于查看元数据
+    static PEEKING: bool;
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 键的访问统计，同一个键的各个版本共享
+#[derive(Debug, Default)]
+struct AccessStats {
+    count: AtomicU64,
+    // 最近一次访问的时间（Unix 毫秒），0 表示从未被读取
+    last_access_ms: AtomicU64,
+}
+
+impl AccessStats {
+    fn record(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.last_access_ms.store(unix_ms(SystemTime::now()), Ordering::Relaxed);
+    }
+}
+
+/// 条目的过期方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TtlMode {
+    // 写入后经过固定时长过期
+    Fixed,
+    // 超过给定时长未被读取才过期
+    Idle,
+    Permanent,
+}
+
+/// 一个键的元数据，用于排查键为何消失或过期
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryMeta {
+    // 键首次写入的时间（Unix 毫秒），覆盖写入不改变
+    pub created_at_ms: u64,
+    // 当前版本写入的时间（Unix 毫秒）
+    pub updated_at_ms: u64,
+    pub last_access_ms: Option<u64>,
+    pub access_count: u64,
+    pub ttl_mode: TtlMode,
+    // 距离过期的剩余时间（毫秒），滑动过期时为估算值，已过期时为 0
+    pub remaining_ttl_ms: Option<u64>,
+    // 实际的过期时间（Unix 毫秒），包含写入时加的 TTL 抖动
+    pub expires_at_ms: Option<u64>,
+    // 已过期，处于宽限期内
+    pub stale: bool,
+    // 估算的内存占用（字节）
+    pub size: u32,
+    pub version: u64,
+    pub tags: Vec<String>,
+}
+
+/// 哈希的字段，按字段名排序
+pub type HashFields = BTreeMap<String, Value>;
+
+/// 缓存中的值：JSON，带内容类型的原始字节，或者数据结构
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheValue {
+    Json(Value),
+    Binary { content_type: String, data: Bytes },
+    // 哈希：可按字段读写的记录
+    Hash(HashFields),
+    // 列表：可从两端压入和弹出
+    List(VecDeque<Value>),
+    // 集合：不重复的字符串成员
+    Set(BTreeSet<String>),
+    // 有序集合：按分数排序的成员
+    SortedSet(SortedSet),
+    // 分布式锁：持有者及其获取时得到的防护令牌
+    Lock(LockState),
+    // 限流的令牌桶
+    Bucket(TokenBucket),
+}
+
+/// 令牌桶在 `updated_ms` 时刻剩余的令牌数，之后按限流参数匀速补充
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    // Unix 毫秒时间戳
+    pub updated_ms: u64,
+}
+
+/// 限流参数：每 `period` 补满 `limit` 个令牌，每次请求消耗 `cost` 个
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub period: Duration,
+    pub cost: u64,
+}
+
+impl RateLimit {
+    /// `cost` 为 0 时按 1 计
+    pub fn new(limit: u64, period: Duration, cost: u64) -> Result<Self, String> {
+        let cost = cost.max(1);
+        if limit == 0 || period.is_zero() {
+            return Err("'limit' and 'period_ms' must be positive".to_string());
+        }
+        if cost > limit {
+            return Err(format!("'cost' {} exceeds 'limit' {}", cost, limit));
+        }
+        Ok(Self { limit, period, cost })
+    }
+
+    /// 每毫秒补充的令牌数
+    fn refill_per_ms(&self) -> f64 {
+        self.limit as f64 / self.period.as_millis() as f64
+    }
+}
+
+/// 一次限流检查的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitOutcome {
+    pub allowed: bool,
+    // 本次检查之后剩余的整数令牌数
+    pub remaining: u64,
+    // 被拒绝时，再等这么久才有足够的令牌；允许时为 0
+    pub retry_after: Duration,
+}
+
+/// 锁的当前持有者
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LockState {
+    pub holder: String,
+    // 获取锁时条目的版本号，之后的每次获取都更大
+    pub token: u64,
+}
+
+impl From<Value> for CacheValue {
+    fn from(value: Value) -> Self {
+        CacheValue::Json(value)
+    }
+}
+
+impl fmt::Display for CacheValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheValue::Json(value) => write!(f, "{}", value),
+            CacheValue::Binary { content_type, data } => {
+                write!(f, "<{} bytes of {}>", data.len(), content_type)
+            }
+            CacheValue::Lock(lock) => write!(f, "<lock held by {} with token {}>", lock.holder, lock.token),
+            CacheValue::Bucket(bucket) => write!(f, "<bucket with {:.2} tokens>", bucket.tokens),
+            value => write!(f, "<{} of {} elements>", value.data_type().unwrap_or_default(), value.len().unwrap_or(0)),
+        }
+    }
+}
+
+impl CacheValue {
+    pub fn as_json(&self) -> Option<&Value> {
+        match self {
+            CacheValue::Json(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// 数据结构的类型名，普通值为 `None`
+    pub fn data_type(&self) -> Option<&'static str> {
+        match self {
+            CacheValue::Json(_) | CacheValue::Binary { .. } => None,
+            CacheValue::Hash(_) => Some("hash"),
+            CacheValue::List(_) => Some("list"),
+            CacheValue::Set(_) => Some("set"),
+            CacheValue::SortedSet(_) => Some("zset"),
+            CacheValue::Lock(_) => Some("lock"),
+            CacheValue::Bucket(_) => Some("bucket"),
+        }
+    }
+
+    /// 锁和令牌桶单独存放，不参与容量驱逐，只能通过各自的接口修改
+    fn is_managed(&self) -> bool {
+        matches!(self, CacheValue::Lock(_) | CacheValue::Bucket(_))
+    }
+
+    /// 令牌桶只是限流的计数，丢失只会让限流重新开始：
+    /// 不记入日志和快照，也不发布变更事件
+    fn is_durable(&self) -> bool {
+        !matches!(self, CacheValue::Bucket(_))
+    }
+
+    /// 数据结构的元素个数，普通值、锁和令牌桶为 `None`
+    fn len(&self) -> Option<usize> {
+        match self {
+            CacheValue::Json(_) | CacheValue::Binary { .. } | CacheValue::Lock(_) | CacheValue::Bucket(_) => None,
+            CacheValue::Hash(fields) => Some(fields.len()),
+            CacheValue::List(items) => Some(items.len()),
+            CacheValue::Set(members) => Some(members.len()),
+            CacheValue::SortedSet(members) => Some(members.len()),
+        }
+    }
+
+    /// 数据结构以 JSON 表示：哈希为对象，列表和集合为数组，
+    /// 有序集合为按分数排序的 `{"member", "score"}` 数组，锁为 `{"holder", "token"}`，
+    /// 令牌桶为 `{"tokens", "updated_ms"}`
+    pub fn to_json(&self) -> Option<Value> {
+        match self {
+            CacheValue::Json(value) => Some(value.clone()),
+            CacheValue::Binary { .. } => None,
+            CacheValue::Hash(fields) => Some(Value::Object(fields.clone().into_iter().collect())),
+            CacheValue::List(items) => Some(Value::Array(items.iter().cloned().collect())),
+            CacheValue::Set(members) => Some(members.iter().cloned().collect()),
+            CacheValue::SortedSet(members) => Some(
+                members
+                    .iter()
+                    .map(|(member, score)| serde_json::json!({"member": member, "score": score}))
+                    .collect(),
+            ),
+            CacheValue::Lock(lock) => serde_json::to_value(lock).ok(),
+            CacheValue::Bucket(bucket) => serde_json::to_value(bucket).ok(),
+        }
+    }
+
+    /// `to_json` 的逆操作，`data_type` 为空时是普通的 JSON 值
+    pub fn from_json(value: Value, data_type: &str) -> Option<Self> {
+        match (data_type, value) {
+            ("", value) => Some(CacheValue::Json(value)),
+            ("hash", Value::Object(fields)) => Some(CacheValue::Hash(fields.into_iter().collect())),
+            ("list", Value::Array(items)) => Some(CacheValue::List(items.into())),
+            ("set", Value::Array(members)) => members
+                .into_iter()
+                .map(|member| match member {
+                    Value::String(member) => Some(member),
+                    _ => None,
+                })
+                .collect::<Option<_>>()
+                .map(CacheValue::Set),
+            ("zset", Value::Array(members)) => members
+                .iter()
+                .map(|entry| Some((entry.get("member")?.as_str()?.to_string(), entry.get("score")?.as_f64()?)))
+                .collect::<Option<_>>()
+                .map(CacheValue::SortedSet),
+            ("lock", value) => Some(CacheValue::Lock(LockState {
+                holder: value.get("holder")?.as_str()?.to_string(),
+                token: value.get("token")?.as_u64()?,
+            })),
+            ("bucket", value) => Some(CacheValue::Bucket(TokenBucket {
+                tokens: value.get("tokens")?.as_f64()?,
+                updated_ms: value.get("updated_ms")?.as_u64()?,
+            })),
+            _ => None,
+        }
+    }
+
+    /// 同类型的空数据结构
+    fn is_empty_collection(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// 估算的字节数：JSON 与数据结构为序列化后的长度，二进制为数据加内容类型的长度
+    fn size(&self) -> usize {
+        match self {
+            CacheValue::Binary { content_type, data } => content_type.len() + data.len(),
+            CacheValue::Json(value) => serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0),
+            value => serde_json::to_vec(&value.to_json()).map(|v| v.len()).unwrap_or(0),
+        }
+    }
+
+    /// 快照中的表示：二进制值保存为 base64 字符串，并附带内容类型；
+    /// 数据结构保存为 JSON，并附带类型名
+    pub fn to_snapshot(&self) -> SnapshotValue {
+        match self {
+            CacheValue::Json(value) => SnapshotValue { value: value.clone(), content_type: None, data_type: None },
+            CacheValue::Binary { content_type, data } => SnapshotValue {
+                value: Value::String(BASE64.encode(data)),
+                content_type: Some(content_type.clone()),
+                data_type: None,
+            },
+            value => SnapshotValue {
+                value: value.to_json().unwrap_or_default(),
+                content_type: None,
+                data_type: value.data_type().map(str::to_string),
+            },
+        }
+    }
+
+    pub fn from_snapshot(snapshot: SnapshotValue) -> Option<Self> {
+        match (snapshot.data_type, snapshot.content_type) {
+            (Some(data_type), _) => CacheValue::from_json(snapshot.value, &data_type),
+            (None, None) => Some(CacheValue::Json(snapshot.value)),
+            (None, Some(content_type)) => {
+                let data = BASE64.decode(snapshot.value.as_str()?).ok()?;
+                Some(CacheValue::Binary { content_type, data: data.into() })
+            }
+        }
+    }
+}
+
+/// 值在快照、日志和后端存储中的表示
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotValue {
+    // 二进制值保存为 base64 字符串，数据结构保存为 JSON
+    pub value: Value,
+    // 二进制值的内容类型，其他值没有该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    // 数据结构的类型名，与内容类型分开保存，任何内容类型都不会被当作数据结构
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
+}
+
+/// 对 JSON 值的局部修改
+#[derive(Debug, Clone)]
+pub enum JsonPatch {
+    // RFC 7396 JSON Merge Patch
+    Merge(Value),
+    // RFC 6902 JSON Patch
+    Operations(json_patch::Patch),
+}
+
+impl JsonPatch {
+    fn apply(&self, doc: &mut Value) -> Result<(), CacheError> {
+        match self {
+            JsonPatch::Merge(patch) => {
+                json_patch::merge(doc, patch);
+                Ok(())
+            }
+            JsonPatch::Operations(patch) => json_patch::patch(doc, patch)
+                .map_err(|e| CacheError::PatchFailed(e.to_string())),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CacheEntry {
-    value: Value,
+    value: CacheValue,
     expires_at: Option<Instant>,
+    // 滑动过期的空闲时长，设置时 `expires_at` 为 `None`
+    idle_timeout: Option<Duration>,
+    // 估算的内存占用（键 + 序列化后的值，字节）
+    size: u32,
+    // 写入版本号，每次写入都单调递增，用于 CAS；
+    // FIFO 策略也用它识别队列中已被覆盖的记录
+    version: u64,
+    // 标签，用于按标签批量失效
+    tags: Arc<[String]>,
+    // 过期后的宽限期，期间条目仍保留在缓存中
+    grace: Grace,
+    // 键首次写入的时间，覆盖写入时沿用
+    created_at: SystemTime,
+    // 当前版本写入的时间
+    updated_at: SystemTime,
+    // 访问统计，覆盖写入时沿用
+    access: Arc<AccessStats>,
 }
 
 impl Hash for CacheEntry {
@@ -25,94 +481,2777 @@ impl Hash for CacheEntry {
     }
 }
 
+impl CacheEntry {
+    fn new(
+        key: &str,
+        value: CacheValue,
+        expires_at: Option<Instant>,
+        idle_timeout: Option<Duration>,
+        version: u64,
+        tags: Arc<[String]>,
+    ) -> Self {
+        let tags_len: usize = tags.iter().map(String::len).sum();
+        let size = u32::try_from(key.len() + value.size() + tags_len).unwrap_or(u32::MAX);
+        let now = SystemTime::now();
+        Self {
+            value,
+            expires_at,
+            idle_timeout,
+            size,
+            version,
+            tags,
+            grace: Grace::default(),
+            created_at: now,
+            updated_at: now,
+            access: Arc::default(),
+        }
+    }
+
+    fn with_grace(mut self, grace: Grace) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// 以新值替换当前条目，保留原有的过期设置和标签
+    fn with_value(&self, key: &str, value: CacheValue, version: u64) -> Self {
+        Self::new(
+            key,
+            value,
+            self.expires_at,
+            self.idle_timeout,
+            version,
+            Arc::clone(&self.tags),
+        )
+        .with_grace(self.grace)
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// 条目从 `now` 起还需在缓存中保留多久（含宽限期），`None` 表示永久
+    fn remaining_ttl(&self, now: Instant) -> Option<Duration> {
+        match self.idle_timeout {
+            Some(idle) => Some(idle),
+            None => self
+                .expires_at
+                .map(|at| (at + self.grace.retain()).saturating_duration_since(now)),
+        }
+    }
+
+    /// 条目在 `now` 时刻已过期多久，未过期时为 `None`
+    fn expired_for(&self, now: Instant) -> Option<Duration> {
+        self.expires_at.filter(|at| now >= *at).map(|at| now - at)
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expired_for(now).is_some()
+    }
+
+    /// 覆盖同一个键的存活条目时，沿用它的创建时间和访问统计
+    fn inherit(&mut self, previous: &CacheEntry) {
+        self.created_at = previous.created_at;
+        self.access = Arc::clone(&previous.access);
+    }
+
+    fn meta(&self, now: Instant) -> EntryMeta {
+        let last_access_ms = match self.access.last_access_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(ms),
+        };
+        let wall_now = SystemTime::now();
+        let (ttl_mode, remaining_ttl) = match (self.idle_timeout, self.expires_at) {
+            (Some(idle), _) => {
+                // 距上次读取或写入的时间
+                let idle_since = last_access_ms
+                    .unwrap_or(0)
+                    .max(unix_ms(self.updated_at));
+                let idle_for = unix_ms(wall_now).saturating_sub(idle_since);
+                (TtlMode::Idle, Some(idle.saturating_sub(Duration::from_millis(idle_for))))
+            }
+            (None, Some(at)) => (TtlMode::Fixed, Some(at.saturating_duration_since(now))),
+            (None, None) => (TtlMode::Permanent, None),
+        };
+        // 已过期的条目按过期了多久往回推
+        let expires_at_ms = match self.expired_for(now) {
+            Some(stale_for) => Some(unix_ms(wall_now - stale_for)),
+            None => remaining_ttl.map(|ttl| unix_ms(wall_now + ttl)),
+        };
+        EntryMeta {
+            created_at_ms: unix_ms(self.created_at),
+            updated_at_ms: unix_ms(self.updated_at),
+            last_access_ms,
+            access_count: self.access.count.load(Ordering::Relaxed),
+            ttl_mode,
+            remaining_ttl_ms: remaining_ttl.map(|ttl| ttl.as_millis() as u64),
+            expires_at_ms,
+            stale: self.is_expired(now),
+            size: self.size,
+            version: self.version,
+            tags: self.tags.to_vec(),
+        }
+    }
+
+    /// 条目在 `now` 时刻的可持久化形式
+    fn snapshot(&self, key: &str, now: Instant) -> EntrySnapshot {
+        EntrySnapshot {
+            key: key.to_string(),
+            value: self.value.to_snapshot(),
+            ttl_ms: self
+                .expires_at
+                .map(|at| at.saturating_duration_since(now).as_millis() as u64),
+            idle_ms: self.idle_timeout.map(|idle| idle.as_millis() as u64),
+            tags: self.tags.to_vec(),
+            version: self.version,
+        }
+    }
+}
+
+/// 把闭区间 `[start, stop]` 的下标（负数从末尾算起）换算为有效的范围，范围为空时为 `None`
+fn index_range(len: usize, start: i64, stop: i64) -> Option<RangeInclusive<usize>> {
+    let len = len as i64;
+    let resolve = |index: i64| if index < 0 { len + index } else { index };
+    let start = resolve(start).max(0);
+    let stop = resolve(stop).min(len - 1);
+    (start <= stop).then_some(start as usize..=stop as usize)
+}
+
+/// 两个 JSON 数值相加：都是整数时按 i128 精确计算，结果超出 i64/u64 时报溢出，
+/// 否则按 f64 计算
+fn add_numbers(a: &Number, b: &Number) -> Result<Number, CacheError> {
+    if let (Some(x), Some(y)) = (integer(a), integer(b)) {
+        return x.checked_add(y).and_then(number_from_i128).ok_or(CacheError::NumericOverflow);
+    }
+    let (Some(x), Some(y)) = (a.as_f64(), b.as_f64()) else {
+        return Err(CacheError::NotNumeric);
+    };
+    Number::from_f64(x + y).ok_or(CacheError::NumericOverflow)
+}
+
+/// JSON 整数（i64 或 u64 范围内）转为 i128，小数为 `None`
+pub fn integer(n: &Number) -> Option<i128> {
+    n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from))
+}
+
+/// i128 转回 JSON 整数，超出 i64 和 u64 范围时为 `None`
+pub fn number_from_i128(n: i128) -> Option<Number> {
+    i64::try_from(n)
+        .map(Number::from)
+        .or_else(|_| u64::try_from(n).map(Number::from))
+        .ok()
+}
+
+/// 让 moka 按照每个条目自身的 `expires_at` 过期，
+/// 过期条目由 moka 在后台清理，而不是等到下一次 `get` 才删除。
+struct CacheEntryExpiry;
+
+impl Expiry<String, CacheEntry> for CacheEntryExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &CacheEntry,
+        created_at: Instant,
+    ) -> Option<Duration> {
+        value.remaining_ttl(created_at)
+    }
+
+    fn expire_after_read(
+        &self,
+        _key: &String,
+        value: &CacheEntry,
+        _read_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        _last_modified_at: Instant,
+    ) -> Option<Duration> {
+        // 滑动过期的条目每次被读取都重新计时，其余条目不受读取影响
+        if PEEKING.try_with(|peeking| *peeking).unwrap_or(false) {
+            return duration_until_expiry;
+        }
+        value.idle_timeout.or(duration_until_expiry)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &CacheEntry,
+        updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        // 覆盖写入时以新条目的 TTL 为准
+        value.remaining_ttl(updated_at)
+    }
+}
+
+/// `update` 中需要记入追加日志的变更
+enum LoggedChange {
+    Set(EntrySnapshot),
+    // 被删除条目的版本号
+    Delete(u64),
+}
+
+/// 事务中暂不对外可见的一次写入：事务全部成功后才记入日志、发布事件、进入写回队列
+struct Deferred {
+    key: String,
+    logged: Option<LoggedChange>,
+    event: Option<(KeyEventKind, u64)>,
+    // 写回队列中的变更，`None` 表示删除
+    write_behind: Option<Option<CacheValue>>,
+    put_version: Option<u64>,
+}
+
+/// 订阅者来不及消费时，广播通道最多缓存的事件数
+const EVENT_CAPACITY: usize = 1024;
+
+/// 键变更事件的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyEventKind {
+    Set,
+    Delete,
+    Expire,
+    Evict,
+}
+
+impl KeyEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyEventKind::Set => "set",
+            KeyEventKind::Delete => "delete",
+            KeyEventKind::Expire => "expire",
+            KeyEventKind::Evict => "evict",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "set" => Some(KeyEventKind::Set),
+            "delete" => Some(KeyEventKind::Delete),
+            "expire" => Some(KeyEventKind::Expire),
+            "evict" => Some(KeyEventKind::Evict),
+            _ => None,
+        }
+    }
+}
+
+/// 键的变更事件，推送给 watch 的订阅者
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyEvent {
+    pub kind: KeyEventKind,
+    pub key: String,
+    // set 为新写入的版本，其余为被移除条目的版本
+    pub version: u64,
+    // 事件发生时的 Unix 毫秒时间戳
+    pub at_ms: u64,
+}
+
+impl KeyEvent {
+    fn new(kind: KeyEventKind, key: &str, version: u64) -> Self {
+        Self { kind, key: key.to_string(), version, at_ms: unix_ms(SystemTime::now()) }
+    }
+}
+
+/// watch 订阅收到的一项
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchItem {
+    Event(KeyEvent),
+    // 订阅者消费太慢，中间丢失了这么多个事件
+    Lagged(u64),
+}
+
+/// 把前缀为 `prefix` 的键的变更转换后发送到 `tx`，直到接收端关闭
+pub async fn forward_events<T>(
+    mut events: broadcast::Receiver<KeyEvent>,
+    prefix: &str,
+    tx: mpsc::Sender<T>,
+    convert: impl Fn(WatchItem) -> T,
+) {
+    loop {
+        let item = tokio::select! {
+            _ = tx.closed() => return,
+            received = events.recv() => match received {
+                Ok(event) if event.key.starts_with(prefix) => WatchItem::Event(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => WatchItem::Lagged(missed),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+        if tx.send(convert(item)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// 槽锁的数量，哈希槽按哈希值分摊到这些锁上
+const SLOT_LOCKS: usize = 64;
+
+/// 事务中的一个操作
+#[derive(Debug, Clone)]
+pub enum TxnOp {
+    Set {
+        key: String,
+        value: CacheValue,
+        ttl: EntryTtl,
+        tags: Vec<String>,
+    },
+    Delete {
+        key: String,
+    },
+    // 要求键的当前版本等于 `version`，0 表示要求键不存在
+    Compare {
+        key: String,
+        version: u64,
+    },
+}
+
+impl TxnOp {
+    pub fn key(&self) -> &str {
+        match self {
+            TxnOp::Set { key, .. } | TxnOp::Delete { key } | TxnOp::Compare { key, .. } => key,
+        }
+    }
+}
+
+/// 条目的权重：启用内存预算时为估算字节数，否则每个条目计 1
+fn entry_weight(memory_budget: bool, entry: &CacheEntry) -> u32 {
+    if memory_budget { entry.size } else { 1 }
+}
+
 #[derive(Debug, Clone)]
 pub struct CacheStore {
     store: Cache<String, CacheEntry>,
+    // 锁和令牌桶：不计入容量，也不会被容量驱逐，与 `store` 共用键名
+    managed: Cache<String, CacheEntry>,
+    // 条目数上限，或在启用内存预算时为字节上限
+    max_capacity: u64,
+    memory_budget: bool,
+    eviction_policy: EvictionPolicy,
     default_ttl: Duration,
+    ttl_jitter: TtlJitter,
+    // 当前已占用的权重，由写入累加、由 moka 的删除通知扣减。
+    // FIFO 与 None 策略下 moka 不限容量，由我们根据它执行上限。
+    used: Arc<AtomicU64>,
+    next_version: Arc<AtomicU64>,
+    // FIFO 策略的写入顺序队列：(键, 写入序号)
+    fifo: Arc<Mutex<VecDeque<(String, u64)>>>,
+    // 追加日志，启用时在启动重放完成后挂上
+    log: Arc<OnceLock<MutationLog>>,
+    // 写回后端存储的队列，启用时在启动恢复完成后挂上
+    write_behind: Arc<OnceLock<WriteBehindQueue>>,
+    // 未命中时回源加载的配置
+    origins: Arc<Origins>,
+    // 正在回源的键，同一个键的并发未命中共享一次加载
+    loading: Arc<Mutex<HashMap<String, LoadCell>>>,
+    // 键变更事件，没有订阅者时发送直接丢弃
+    events: broadcast::Sender<KeyEvent>,
+    // 单键的读写持有所在槽的读锁，事务持有写锁，事务执行期间同一个槽上的读写等待它结束
+    slot_locks: Arc<[RwLock<()>]>,
 }
 
+type LoadResult = Result<Option<(CacheValue, u64)>, CacheError>;
+type ReadResult = Result<Option<(CacheValue, u64, Freshness)>, CacheError>;
+type LoadCell = Arc<OnceCell<LoadResult>>;
+
 pub type SharedCache = Arc<CacheStore>;
 
+/// 快照中保存的一个条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntrySnapshot {
+    pub key: String,
+    #[serde(flatten)]
+    pub value: SnapshotValue,
+    // 快照时的剩余存活时间（毫秒），`None` 表示永久或滑动过期
+    pub ttl_ms: Option<u64>,
+    // 滑动过期的空闲时长（毫秒）
+    pub idle_ms: Option<u64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub version: u64,
+}
+
+/// 节点缓存的统计信息，用于容量规划
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entry_count: u64,
+    // 启用内存预算时为估算字节数，否则等于条目数
+    pub weighted_size: u64,
+    pub max_capacity: u64,
+    pub memory_budget: bool,
+    pub eviction_policy: EvictionPolicy,
+}
+
 
 impl CacheStore {
-    pub fn new(capacity: u64, default_ttl_seconds: u64) -> Self {
-        let cache = Cache::builder()
-            .max_capacity(capacity)
+    pub fn new(settings: &CacheSettings) -> Self {
+        // 配置了 max_memory_bytes 时按条目大小加权驱逐，否则按条目数
+        let (max_capacity, memory_budget) = match settings.max_memory_bytes {
+            Some(bytes) => (bytes, true),
+            None => (settings.capacity, false),
+        };
+
+        let used = Arc::new(AtomicU64::new(0));
+        let used_listener = Arc::clone(&used);
+        let log = Arc::new(OnceLock::<MutationLog>::new());
+        let log_listener = Arc::clone(&log);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let events_listener = events.clone();
+        let managed_listener = events.clone();
+
+        let mut builder = Cache::builder()
+            .expire_after(CacheEntryExpiry)
+            .weigher(move |_key: &String, entry: &CacheEntry| entry_weight(memory_budget, entry))
+            .eviction_listener(move |key: Arc<String>, entry: CacheEntry, cause: RemovalCause| {
+                let weight = entry_weight(memory_budget, &entry) as u64;
+                used_listener.fetch_sub(weight, Ordering::Relaxed);
+                // 容量驱逐也要记入日志，否则重放时被驱逐的键会复活
+                if cause == RemovalCause::Size
+                    && let Some(log) = log_listener.get()
+                {
+                    log.append_delete(&key, entry.version);
+                }
+                // 显式删除在 `apply` 中发布，这里只发布 moka 自行移除的条目
+                let kind = match cause {
+                    RemovalCause::Expired => KeyEventKind::Expire,
+                    RemovalCause::Size => KeyEventKind::Evict,
+                    RemovalCause::Explicit | RemovalCause::Replaced => return,
+                };
+                let _ = events_listener.send(KeyEvent::new(kind, &key, entry.version));
+            });
+        builder = match settings.eviction_policy {
+            EvictionPolicy::Lru => builder
+                .eviction_policy(MokaEvictionPolicy::lru())
+                .max_capacity(max_capacity),
+            EvictionPolicy::TinyLfu => builder
+                .eviction_policy(MokaEvictionPolicy::tiny_lfu())
+                .max_capacity(max_capacity),
+            // 这两种策略 moka 不支持，由 `set` 自行执行容量上限
+            EvictionPolicy::Fifo | EvictionPolicy::None => builder,
+        };
+        let managed = Cache::builder()
+            .expire_after(CacheEntryExpiry)
+            .eviction_listener(move |key: Arc<String>, entry: CacheEntry, cause: RemovalCause| {
+                if cause == RemovalCause::Expired && entry.value.is_durable() {
+                    let _ = managed_listener.send(KeyEvent::new(KeyEventKind::Expire, &key, entry.version));
+                }
+            })
             .build();
         // let cache = Cache::new(capacity);
         Self { 
-            store: cache,
-            default_ttl: Duration::from_secs(default_ttl_seconds)
+            store: builder.build(),
+            managed,
+            max_capacity,
+            memory_budget,
+            eviction_policy: settings.eviction_policy,
+            default_ttl: Duration::from_secs(settings.default_ttl_seconds),
+            ttl_jitter: TtlJitter::new(settings),
+            used,
+            next_version: Arc::new(AtomicU64::new(1)),
+            fifo: Arc::new(Mutex::new(VecDeque::new())),
+            log,
+            write_behind: Arc::new(OnceLock::new()),
+            origins: Arc::new(Origins::default()),
+            loading: Arc::new(Mutex::new(HashMap::new())),
+            events,
+            slot_locks: (0..SLOT_LOCKS).map(|_| RwLock::new(())).collect(),
         }
     }
 
-    pub async fn set(&self, key: String, value: Value, ttl: CacheItemTTL) {
+    pub fn with_origins(mut self, origins: Origins) -> Self {
+        self.origins = Arc::new(origins);
+        self
+    }
+
+    /// 挂上追加日志，之后的每次写入和删除都会被记录
+    pub fn attach_log(&self, log: MutationLog) {
+        let _ = self.log.set(log);
+    }
+
+    /// 订阅本缓存中所有键的变更事件
+    pub fn subscribe(&self) -> broadcast::Receiver<KeyEvent> {
+        self.events.subscribe()
+    }
+
+    /// 挂上写回队列，之后客户端的写入和删除都会异步写入后端存储
+    pub fn attach_write_behind(&self, queue: WriteBehindQueue) {
+        let _ = self.write_behind.set(queue);
+    }
+
+    /// 写入一个键值对，返回新的版本号。覆盖写入时标签以本次为准。
+    pub async fn set(
+        &self,
+        key: String,
+        value: impl Into<CacheValue>,
+        ttl: impl Into<EntryTtl>,
+        tags: Vec<String>,
+    ) -> Result<u64, CacheError> {
         // self.store.insert(key, value).await;
-        let expires_at = match ttl {
-            CacheItemTTL::Default => Some(Instant::now() + self.default_ttl),
-            CacheItemTTL::Permanent => None,
-            CacheItemTTL::Custom(dur) => Some(Instant::now() + dur),
-        };
-        let entry = CacheEntry { value, expires_at };
-        self.store.insert(key, entry).await;
+        let entry = self.new_entry(&key, value.into(), ttl.into(), tags);
+        self.update(&key, |_current| {
+            let version = entry.version;
+            Ok((Op::Put(entry), version))
+        })
+        .await
     }
 
-    pub async fn get(&self, key: &str) -> Option<Value> {
-        let Some(entry) = self.store.get(key).await else {
-            return None;
-        };
-        if let Some(expiration) = entry.expires_at {
-            if Instant::now() >= expiration {
-                self.store.remove(key).await;
-                return None;
+    /// 仅当键的当前版本等于 `expected_version` 时才写入，返回新的版本号。
+    /// `expected_version` 为 0 表示要求键当前不存在。
+    pub async fn compare_and_set(
+        &self,
+        key: String,
+        value: impl Into<CacheValue>,
+        ttl: impl Into<EntryTtl>,
+        tags: Vec<String>,
+        expected_version: u64,
+    ) -> Result<u64, CacheError> {
+        let entry = self.new_entry(&key, value.into(), ttl.into(), tags);
+        self.update(&key, |current| {
+            let current_version = current.map_or(0, |e| e.version);
+            if current_version != expected_version {
+                return Err(CacheError::VersionMismatch {
+                    expected: expected_version,
+                    current: current_version,
+                });
             }
-        }
-        Some(entry.value.clone())
+            let version = entry.version;
+            Ok((Op::Put(entry), version))
+        })
+        .await
     }
 
-    pub async fn delete(&self, key: &str) -> i64 {
-        match self.store.remove(key).await {
-            Some(_) => 1,
-            None => 0,  
-        }
+    /// 原子地为数值加上 `delta`，返回新值及其版本号。
+    /// 键不存在时以 `initial` 为初值并使用 `ttl` 创建；已存在的键保留原有的过期设置。
+    pub async fn increment(
+        &self,
+        key: String,
+        delta: Number,
+        initial: Number,
+        ttl: impl Into<EntryTtl>,
+    ) -> Result<(Number, u64), CacheError> {
+        self.update(&key, |current| {
+            let (result, entry) = match current {
+                Some(entry) => {
+                    let Some(Value::Number(number)) = entry.value.as_json() else {
+                        return Err(CacheError::NotNumeric);
+                    };
+                    let result = add_numbers(number, &delta)?;
+                    let value = CacheValue::Json(Value::Number(result.clone()));
+                    (result, entry.with_value(&key, value, self.next_version()))
+                }
+                None => {
+                    let result = add_numbers(&initial, &delta)?;
+                    let value = CacheValue::Json(Value::Number(result.clone()));
+                    (result, self.new_entry(&key, value, ttl.into(), Vec::new()))
+                }
+            };
+            let version = entry.version;
+            Ok((Op::Put(entry), (result, version)))
+        })
+        .await
     }
-}
 
+    /// 在当前值上原子地应用补丁，返回新值及其版本号。
+    /// 补丁失败时值保持不变；条目保留原有的过期设置。
+    pub async fn patch(&self, key: &str, patch: &JsonPatch) -> Result<(Value, u64), CacheError> {
+        self.update(key, |current| {
+            let entry = current.ok_or(CacheError::KeyNotFound)?;
+            let mut value = entry
+                .value
+                .as_json()
+                .cloned()
+                .ok_or_else(|| CacheError::PatchFailed("value is not JSON".to_string()))?;
+            patch.apply(&mut value)?;
+            let entry = entry.with_value(key, CacheValue::Json(value.clone()), self.next_version());
+            let version = entry.version;
+            Ok((Op::Put(entry), (value, version)))
+        })
+        .await
+    }
 
-// --- 单元测试 ---
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
+    /// 只修改键的过期设置，值、标签和版本号都保持不变，返回版本号。
+    /// 版本号标识的是值，持有 ETag 的客户端不会因为 TTL 变化而 412。
+    /// 过期设置只影响缓存，不写回后端存储。
+    pub async fn expire(&self, key: &str, ttl: CacheItemTTL) -> Result<u64, CacheError> {
+        let (expires_at, idle_timeout) = self.expiry_for(ttl);
+        self.update_local(key, |current| {
+            let current = current.ok_or(CacheError::KeyNotFound)?;
+            let entry = CacheEntry::new(
+                key,
+                current.value.clone(),
+                expires_at,
+                idle_timeout,
+                current.version,
+                Arc::clone(&current.tags),
+            )
+            .with_grace(current.grace);
+            let version = entry.version;
+            Ok((Op::Put(entry), version))
+        })
+        .await
+    }
 
-    fn create_test_cache(capacity: u64, default_ttl_sec: u64) -> CacheStore {
-        CacheStore::new(capacity, default_ttl_sec)
+    /// 键的过期方式和剩余存活时间（永久的键为 `None`）。查看 TTL 不算一次访问。
+    pub async fn ttl(&self, key: &str) -> Option<(TtlMode, Option<Duration>)> {
+        let meta = self.meta(key).await.filter(|meta| !meta.stale)?;
+        Some((meta.ttl_mode, meta.remaining_ttl_ms.map(Duration::from_millis)))
     }
-    
-    #[tokio::test]
-    async fn test_lru_eviction_still_works() {
-        let cache = create_test_cache(2, 60); // 容量为 2
 
-        // 插入 3 个永久条目
-        cache.set("key1".to_string(), json!(1), CacheItemTTL::Permanent).await;
-        cache.store.run_pending_tasks().await;
-        cache.set("key2".to_string(), json!(2), CacheItemTTL::Permanent).await;
-        cache.store.run_pending_tasks().await;
-        cache.set("key3".to_string(), json!(3), CacheItemTTL::Permanent).await; 
-        cache.store.run_pending_tasks().await;
-        cache.set("key4".to_string(), json!(4), CacheItemTTL::Permanent).await;
-        cache.store.run_pending_tasks().await;
-        cache.set("key5".to_string(), json!(5), CacheItemTTL::Permanent).await;
+    /// 读取哈希的一个字段，键或字段不存在时为 `None`
+    pub async fn hash_get(&self, key: &str, field: &str) -> Result<Option<Value>, CacheError> {
+        let value = self
+            .read_value(key, |value| match value {
+                CacheValue::Hash(fields) => Ok(fields.get(field).cloned()),
+                _ => Err(CacheError::WrongType),
+            })
+            .await?;
+        Ok(value.flatten())
+    }
 
-        cache.store.run_pending_tasks().await;
+    /// 读取哈希的所有字段
+    pub async fn hash_get_all(&self, key: &str) -> Result<Option<HashFields>, CacheError> {
+        self.read_value(key, |value| match value {
+            CacheValue::Hash(fields) => Ok(fields.clone()),
+            _ => Err(CacheError::WrongType),
+        })
+        .await
+    }
 
-        let count = cache.store.entry_count();
-        println!("当前缓存条目数: {}", count);
+    /// 设置哈希的一个字段，返回字段是否新建以及新的版本号。
+    /// 键不存在时使用 `ttl` 创建哈希；已存在的键保留原有的过期设置。
+    pub async fn hash_set(
+        &self,
+        key: String,
+        field: String,
+        value: Value,
+        ttl: impl Into<EntryTtl>,
+    ) -> Result<(bool, u64), CacheError> {
+        self.update_collection(&key, ttl.into(), CacheValue::Hash(HashFields::new()), |current| {
+            let CacheValue::Hash(fields) = current else {
+                return Err(CacheError::WrongType);
+            };
+            Ok(fields.insert(field, value).is_none())
+        })
+        .await
+    }
 
-        // cache.store.run_pending_tasks().await;
+    /// 删除哈希的一个字段，返回字段是否存在
+    pub async fn hash_delete(&self, key: &str, field: &str) -> Result<bool, CacheError> {
+        let (deleted, _) = self
+            .update_collection(key, CacheItemTTL::Default.into(), CacheValue::Hash(HashFields::new()), |current| {
+                let CacheValue::Hash(fields) = current else {
+                    return Err(CacheError::WrongType);
+                };
+                Ok(fields.remove(field).is_some())
+            })
+            .await?;
+        Ok(deleted)
+    }
 
-        assert_eq!(cache.get("key1").await, None, "key1 应该被 LRU 驱逐");
-        assert_eq!(cache.get("key2").await, Some(json!(2)));
-        assert_eq!(cache.get("key3").await, Some(json!(3)));
+    /// 在列表的一端压入值，返回压入后的长度以及新的版本号。
+    /// 从头部压入时按给定的顺序逐个压入，结果与之相反。
+    pub async fn list_push(
+        &self,
+        key: String,
+        values: Vec<Value>,
+        end: ListEnd,
+        ttl: impl Into<EntryTtl>,
+    ) -> Result<(u64, u64), CacheError> {
+        self.update_collection(&key, ttl.into(), CacheValue::List(VecDeque::new()), |current| {
+            let CacheValue::List(items) = current else {
+                return Err(CacheError::WrongType);
+            };
+            for value in values {
+                match end {
+                    ListEnd::Front => items.push_front(value),
+                    ListEnd::Back => items.push_back(value),
+                }
+            }
+            Ok(items.len() as u64)
+        })
+        .await
+    }
+
+    /// 从列表的一端弹出至多 `count` 个值
+    pub async fn list_pop(&self, key: &str, end: ListEnd, count: usize) -> Result<Vec<Value>, CacheError> {
+        let (popped, _) = self
+            .update_collection(key, CacheItemTTL::Default.into(), CacheValue::List(VecDeque::new()), |current| {
+                let CacheValue::List(items) = current else {
+                    return Err(CacheError::WrongType);
+                };
+                let count = count.min(items.len());
+                Ok(match end {
+                    ListEnd::Front => items.drain(..count).collect(),
+                    ListEnd::Back => items.drain(items.len() - count..).rev().collect(),
+                })
+            })
+            .await?;
+        Ok(popped)
+    }
+
+    /// 读取列表下标在 `[start, stop]` 之间的值，负数下标从末尾算起
+    pub async fn list_range(&self, key: &str, start: i64, stop: i64) -> Result<Vec<Value>, CacheError> {
+        let values = self
+            .read_value(key, |value| match value {
+                CacheValue::List(items) => Ok(index_range(items.len(), start, stop)
+                    .map(|range| items.range(range).cloned().collect())
+                    .unwrap_or_default()),
+                _ => Err(CacheError::WrongType),
+            })
+            .await?;
+        Ok(values.unwrap_or_default())
+    }
+
+    /// 只保留列表下标在 `[start, stop]` 之间的值，返回剩余的长度
+    pub async fn list_trim(&self, key: &str, start: i64, stop: i64) -> Result<u64, CacheError> {
+        let (len, _) = self
+            .update_collection(key, CacheItemTTL::Default.into(), CacheValue::List(VecDeque::new()), |current| {
+                let CacheValue::List(items) = current else {
+                    return Err(CacheError::WrongType);
+                };
+                match index_range(items.len(), start, stop) {
+                    Some(range) => {
+                        items.truncate(range.end() + 1);
+                        items.drain(..range.start());
+                    }
+                    None => items.clear(),
+                }
+                Ok(items.len() as u64)
+            })
+            .await?;
+        Ok(len)
+    }
+
+    /// 向集合添加成员，返回新加入的成员数以及新的版本号
+    pub async fn set_add(
+        &self,
+        key: String,
+        members: Vec<String>,
+        ttl: impl Into<EntryTtl>,
+    ) -> Result<(u64, u64), CacheError> {
+        self.update_collection(&key, ttl.into(), CacheValue::Set(BTreeSet::new()), |current| {
+            let CacheValue::Set(set) = current else {
+                return Err(CacheError::WrongType);
+            };
+            Ok(members.into_iter().filter(|member| set.insert(member.clone())).count() as u64)
+        })
+        .await
+    }
+
+    /// 从集合移除成员，返回实际移除的数量
+    pub async fn set_remove(&self, key: &str, members: &[String]) -> Result<u64, CacheError> {
+        let (removed, _) = self
+            .update_collection(key, CacheItemTTL::Default.into(), CacheValue::Set(BTreeSet::new()), |current| {
+                let CacheValue::Set(set) = current else {
+                    return Err(CacheError::WrongType);
+                };
+                Ok(members.iter().filter(|member| set.remove(*member)).count() as u64)
+            })
+            .await?;
+        Ok(removed)
     }
-}
\ No newline at end of file
+
+    pub async fn set_contains(&self, key: &str, member: &str) -> Result<bool, CacheError> {
+        let found = self
+            .read_value(key, |value| match value {
+                CacheValue::Set(set) => Ok(set.contains(member)),
+                _ => Err(CacheError::WrongType),
+            })
+            .await?;
+        Ok(found.unwrap_or(false))
+    }
+
+    /// 集合的所有成员，按字典序排列
+    pub async fn set_members(&self, key: &str) -> Result<Vec<String>, CacheError> {
+        let members = self
+            .read_value(key, |value| match value {
+                CacheValue::Set(set) => Ok(set.iter().cloned().collect()),
+                _ => Err(CacheError::WrongType),
+            })
+            .await?;
+        Ok(members.unwrap_or_default())
+    }
+
+    /// 向有序集合添加成员或更新其分数，返回新加入的成员数以及新的版本号
+    pub async fn zset_add(
+        &self,
+        key: String,
+        members: Vec<(String, f64)>,
+        ttl: impl Into<EntryTtl>,
+    ) -> Result<(u64, u64), CacheError> {
+        self.update_collection(&key, ttl.into(), CacheValue::SortedSet(SortedSet::default()), |current| {
+            let CacheValue::SortedSet(set) = current else {
+                return Err(CacheError::WrongType);
+            };
+            Ok(members
+                .into_iter()
+                .filter(|(member, score)| set.insert(member.clone(), *score))
+                .count() as u64)
+        })
+        .await
+    }
+
+    /// 从有序集合移除成员，返回实际移除的数量
+    pub async fn zset_remove(&self, key: &str, members: &[String]) -> Result<u64, CacheError> {
+        let (removed, _) = self
+            .update_collection(key, CacheItemTTL::Default.into(), CacheValue::SortedSet(SortedSet::default()), |current| {
+                let CacheValue::SortedSet(set) = current else {
+                    return Err(CacheError::WrongType);
+                };
+                Ok(members.iter().filter(|member| set.remove(member)).count() as u64)
+            })
+            .await?;
+        Ok(removed)
+    }
+
+    /// 成员的排名（从 0 开始）和分数，`rev` 时按分数从大到小排名
+    pub async fn zset_rank(&self, key: &str, member: &str, rev: bool) -> Result<Option<(u64, f64)>, CacheError> {
+        let rank = self
+            .read_value(key, |value| match value {
+                CacheValue::SortedSet(set) => Ok(set.rank(member, rev)),
+                _ => Err(CacheError::WrongType),
+            })
+            .await?;
+        Ok(rank.flatten().map(|(rank, score)| (rank as u64, score)))
+    }
+
+    /// 按排名或分数读取有序集合的一段成员及分数，`rev` 时按分数从大到小排列
+    pub async fn zset_range(&self, key: &str, range: ZsetRange, rev: bool) -> Result<Vec<(String, f64)>, CacheError> {
+        let members = self
+            .read_value(key, |value| {
+                let CacheValue::SortedSet(set) = value else {
+                    return Err(CacheError::WrongType);
+                };
+                Ok(match range {
+                    ZsetRange::Rank { start, stop } => index_range(set.len(), start, stop)
+                        .map(|range| set.range_by_rank(range, rev))
+                        .unwrap_or_default(),
+                    ZsetRange::Score { min, max } => set.range_by_score(min, max, rev),
+                })
+            })
+            .await?;
+        Ok(members.unwrap_or_default())
+    }
+
+    /// 数据结构的元素个数，键不存在时为 0
+    pub async fn len(&self, key: &str) -> Result<u64, CacheError> {
+        let len = self
+            .read_value(key, |value| value.len().ok_or(CacheError::WrongType))
+            .await?;
+        Ok(len.unwrap_or(0) as u64)
+    }
+
+    /// 获取锁并返回防护令牌，租约到期后锁自动释放。
+    /// 锁已被持有时返回 `LockHeld`，即使请求者就是当前持有者。
+    /// 令牌即条目的版本号，重启后从快照和日志中见过的最大版本号（包括重写日志时记下的高水位）继续递增。
+    /// 锁单独存放，不会被容量驱逐，普通的写入和删除也不能改动它。
+    pub async fn lock_acquire(&self, key: String, holder: String, lease: Duration) -> Result<u64, CacheError> {
+        if lease.is_zero() {
+            return Err(CacheError::ZeroLease);
+        }
+        self.update_managed(&key, |current| {
+            match current.map(|entry| &entry.value) {
+                Some(CacheValue::Lock(lock)) => return Err(CacheError::LockHeld(lock.holder.clone())),
+                Some(_) => return Err(CacheError::WrongType),
+                None => {}
+            }
+            // 版本号全局递增，直接用作令牌
+            let version = self.next_version();
+            let lock = CacheValue::Lock(LockState { holder, token: version });
+            let (expires_at, _) = self.expiry_for(CacheItemTTL::Custom(lease));
+            let entry = CacheEntry::new(&key, lock, expires_at, None, version, Vec::new().into());
+            Ok((Op::Put(entry), version))
+        })
+        .await
+    }
+
+    /// 以令牌续租，租约从现在起重新计算。令牌不是当前持有者的时返回 `NotLockHolder`。
+    /// 租约为 0 时返回 `ZeroLease`，获取锁时同样如此。
+    pub async fn lock_renew(&self, key: &str, token: u64, lease: Duration) -> Result<(), CacheError> {
+        if lease.is_zero() {
+            return Err(CacheError::ZeroLease);
+        }
+        self.update_managed(key, |current| {
+            let current = Self::held_lock(current, token)?;
+            let (expires_at, _) = self.expiry_for(CacheItemTTL::Custom(lease));
+            let entry = CacheEntry::new(
+                key,
+                current.value.clone(),
+                expires_at,
+                None,
+                self.next_version(),
+                Arc::clone(&current.tags),
+            );
+            Ok((Op::Put(entry), ()))
+        })
+        .await
+    }
+
+    /// 以令牌释放锁，令牌不是当前持有者的时返回 `NotLockHolder`
+    pub async fn lock_release(&self, key: &str, token: u64) -> Result<(), CacheError> {
+        self.update_managed(key, |current| {
+            Self::held_lock(current, token)?;
+            Ok((Op::Remove, ()))
+        })
+        .await
+    }
+
+    /// 锁的当前持有者及租约的剩余时间，未被持有时为 `None`。查看锁不算一次访问。
+    pub async fn lock_info(&self, key: &str) -> Result<Option<(LockState, Duration)>, CacheError> {
+        let now = Instant::now();
+        let Some(entry) = self.managed.get(key).await.filter(|entry| !entry.is_expired(now)) else {
+            // 键名被普通的值占用
+            return match self.store.get(key).await.filter(|entry| !entry.is_expired(now)) {
+                Some(_) => Err(CacheError::WrongType),
+                None => Ok(None),
+            };
+        };
+        let CacheValue::Lock(lock) = &entry.value else {
+            return Err(CacheError::WrongType);
+        };
+        let remaining = entry.expires_at.map_or(Duration::ZERO, |at| at.saturating_duration_since(now));
+        Ok(Some((lock.clone(), remaining)))
+    }
+
+    /// 在键级锁内检查并消耗令牌桶中的 `cost` 个令牌，键不存在时桶是满的。
+    /// 桶的存活时间为补满所需的时间，到期后由 TTL 清理，相当于回到满的状态；
+    /// 被拒绝时不写入。桶与锁一样单独存放，不会被容量驱逐；
+    /// 桶只保存在内存中，重启后限流重新开始，更新时沿用原来的版本号。
+    pub async fn rate_limit(&self, key: String, limit: RateLimit) -> Result<RateLimitOutcome, CacheError> {
+        let now_ms = unix_ms(SystemTime::now());
+        let capacity = limit.limit as f64;
+        let cost = limit.cost as f64;
+        let refill = limit.refill_per_ms();
+
+        self.update_managed(&key, |current| {
+            let tokens = match current.map(|entry| &entry.value) {
+                Some(CacheValue::Bucket(bucket)) => {
+                    let elapsed = now_ms.saturating_sub(bucket.updated_ms) as f64;
+                    (bucket.tokens + elapsed * refill).min(capacity)
+                }
+                Some(_) => return Err(CacheError::WrongType),
+                None => capacity,
+            };
+
+            if tokens < cost {
+                let retry_after = Duration::from_millis(((cost - tokens) / refill).ceil() as u64);
+                let outcome = RateLimitOutcome { allowed: false, remaining: tokens as u64, retry_after };
+                return Ok((Op::Nop, outcome));
+            }
+
+            let tokens = tokens - cost;
+            let outcome = RateLimitOutcome { allowed: true, remaining: tokens as u64, retry_after: Duration::ZERO };
+            let until_full = Duration::from_millis(((capacity - tokens) / refill).ceil() as u64);
+            if until_full.is_zero() {
+                return Ok((Op::Nop, outcome));
+            }
+            let bucket = CacheValue::Bucket(TokenBucket { tokens, updated_ms: now_ms });
+            let (expires_at, _) = self.expiry_for(CacheItemTTL::Custom(until_full));
+            let version = current.map_or_else(|| self.next_version(), |entry| entry.version);
+            let entry = CacheEntry::new(&key, bucket, expires_at, None, version, Vec::new().into());
+            Ok((Op::Put(entry), outcome))
+        })
+        .await
+    }
+
+    /// 检查条目是令牌为 `token` 的锁
+    fn held_lock(current: Option<&CacheEntry>, token: u64) -> Result<&CacheEntry, CacheError> {
+        match current {
+            Some(entry) => match &entry.value {
+                CacheValue::Lock(lock) if lock.token == token => Ok(entry),
+                CacheValue::Lock(_) => Err(CacheError::NotLockHolder),
+                _ => Err(CacheError::WrongType),
+            },
+            None => Err(CacheError::NotLockHolder),
+        }
+    }
+
+    /// 读取存活的值并交给 `f`，键不存在时为 `None`
+    async fn read_value<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&CacheValue) -> Result<T, CacheError>,
+    ) -> Result<Option<T>, CacheError> {
+        let now = Instant::now();
+        match self.read(key).await.filter(|entry| !entry.is_expired(now)) {
+            Some(entry) => f(&entry.value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// 在键级锁内修改数据结构，返回 `f` 的结果以及修改后的版本号。
+    /// 键不存在时从 `empty` 开始，写入时使用 `ttl` 创建；已存在的键保留原有的过期设置。
+    /// 修改之后为空时删除键，没有变化时不写入。
+    ///
+    /// 缓存中的条目不可变，每次修改都要复制整个数据结构并重新估算大小，
+    /// 开销与元素个数成正比，因此元素个数限制在 `MAX_COLLECTION_LEN` 以内。
+    async fn update_collection<T>(
+        &self,
+        key: &str,
+        ttl: EntryTtl,
+        empty: CacheValue,
+        f: impl FnOnce(&mut CacheValue) -> Result<T, CacheError>,
+    ) -> Result<(T, u64), CacheError> {
+        self.update(key, |current| {
+            let mut value = current.map_or(empty, |entry| entry.value.clone());
+            let out = f(&mut value)?;
+            // 只拒绝让数据结构变大的修改，已经超限的数据结构仍可缩小
+            let before = current.and_then(|entry| entry.value.len()).unwrap_or(0);
+            if value.len().is_some_and(|len| len > MAX_COLLECTION_LEN && len > before) {
+                return Err(CacheError::CollectionTooLarge);
+            }
+            let op = match current {
+                Some(_) if value.is_empty_collection() => Op::Remove,
+                None if value.is_empty_collection() => Op::Nop,
+                Some(entry) if entry.value == value => Op::Nop,
+                Some(entry) => Op::Put(entry.with_value(key, value, self.next_version())),
+                None => Op::Put(self.new_entry(key, value, ttl, Vec::new())),
+            };
+            let version = match &op {
+                Op::Put(entry) => entry.version,
+                _ => current.map_or(0, |entry| entry.version),
+            };
+            Ok((op, (out, version)))
+        })
+        .await
+    }
+
+    /// 读取 JSON 值，二进制值返回 `None`
+    #[cfg(test)]
+    pub async fn get(&self, key: &str) -> Option<Value> {
+        match self.get_versioned(key).await {
+            Some((CacheValue::Json(value), _)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// 读取值及其当前版本号
+    pub async fn get_versioned(&self, key: &str) -> Option<(CacheValue, u64)> {
+        // moka 不会返回超出保留期的条目，读取同时会延长滑动过期条目的寿命；
+        // 宽限期内的条目已过期，不返回
+        let now = Instant::now();
+        self.read(key)
+            .await
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| (entry.value, entry.version))
+    }
+
+    /// 读取条目并记入访问统计
+    async fn read(&self, key: &str) -> Option<CacheEntry> {
+        // 与事务互斥，不会读到事务中途的状态
+        let _slot = self.slot_lock(key).read().await;
+        let entry = match self.managed.get(key).await {
+            Some(entry) => entry,
+            None => self.store.get(key).await?,
+        };
+        entry.access.record();
+        Some(entry)
+    }
+
+    /// 键的元数据，包括宽限期内的过期条目。查看元数据不算一次访问。
+    pub async fn meta(&self, key: &str) -> Option<EntryMeta> {
+        let _slot = self.slot_lock(key).read().await;
+        let entry = match self.managed.get(key).await {
+            Some(entry) => entry,
+            None => PEEKING.scope(true, self.store.get(key)).await?,
+        };
+        Some(entry.meta(Instant::now()))
+    }
+
+    /// 读取值，未命中且配置了回源时从源加载并写入缓存（read-through）。
+    /// 已过期但仍在宽限期内的条目：stale-while-revalidate 期间直接返回旧值并在后台刷新；
+    /// 之后同步刷新，源不可用时在 stale-if-error 期限内仍返回旧值。
+    pub async fn get_or_load(&self, key: &str) -> ReadResult {
+        let now = Instant::now();
+        let entry = self.read(key).await;
+        if let Some(entry) = &entry
+            && !entry.is_expired(now)
+        {
+            return Ok(Some((entry.value.clone(), entry.version, Freshness::Fresh)));
+        }
+        let Some(origin) = self.origins.find(key) else {
+            return Ok(None);
+        };
+        let stale = entry.and_then(|entry| Some((entry.expired_for(now)?, entry)));
+
+        match stale {
+            Some((expired_for, entry)) if expired_for < entry.grace.revalidate => {
+                self.spawn_refresh(key);
+                Ok(Some((entry.value, entry.version, Freshness::Stale)))
+            }
+            Some((expired_for, entry)) => match self.load(key, origin).await {
+                Ok(loaded) => Ok(loaded.map(|(value, version)| (value, version, Freshness::Fresh))),
+                Err(e) if expired_for < entry.grace.if_error => {
+                    warn!("Refreshing key '{}' failed, serving stale value: {}", key, e);
+                    Ok(Some((entry.value, entry.version, Freshness::StaleIfError)))
+                }
+                Err(e) => Err(e),
+            },
+            None => {
+                let loaded = self.load(key, origin).await?;
+                Ok(loaded.map(|(value, version)| (value, version, Freshness::Fresh)))
+            }
+        }
+    }
+
+    /// 在后台刷新一个宽限期内的键，已有加载在进行时不再发起
+    fn spawn_refresh(&self, key: &str) {
+        if self.loading.lock().unwrap().contains_key(key) {
+            return;
+        }
+        let cache = self.clone();
+        let key = key.to_string();
+        tokio::spawn(async move {
+            let Some(origin) = cache.origins.find(&key) else {
+                return;
+            };
+            if let Err(e) = cache.load(&key, origin).await {
+                warn!("Background refresh of key '{}' failed: {}", key, e);
+            }
+        });
+    }
+
+    /// 从源加载一个键并写入缓存。
+    /// 同一个键的并发加载只会向源发起一次请求，其余请求等待它的结果。
+    async fn load(&self, key: &str, origin: &Origin) -> LoadResult {
+        let cell = Arc::clone(self.loading.lock().unwrap().entry(key.to_string()).or_default());
+        let result = cell
+            .get_or_init(|| async {
+                // 排队期间键可能已被加载或写入
+                if let Some(hit) = self.get_versioned(key).await {
+                    return Ok(Some(hit));
+                }
+                let Some(value) = origin.fetch(key).await? else {
+                    // 源中已不存在：不写入新值，宽限期内的旧值随之移除
+                    self.update_local(key, |_| Ok((Op::Nop, ()))).await?;
+                    return Ok(None);
+                };
+                // 只在键仍不存在（或已过期）时写入，避免覆盖加载期间客户端写入的新值；
+                // 加载的值来自源，不写回后端存储
+                let entry = self.new_entry(key, value.clone(), origin.ttl().into(), Vec::new());
+                let filled = self
+                    .update_local(key, |current| {
+                        Ok(match current {
+                            Some(_) => (Op::Nop, None),
+                            None => {
+                                let version = entry.version;
+                                (Op::Put(entry), Some(version))
+                            }
+                        })
+                    })
+                    .await?;
+                match filled {
+                    Some(version) => Ok(Some((value, version))),
+                    None => Ok(self.get_versioned(key).await),
+                }
+            })
+            .await
+            .clone();
+
+        // 加载结束后移除，之后的未命中重新回源
+        let mut loading = self.loading.lock().unwrap();
+        if loading.get(key).is_some_and(|current| Arc::ptr_eq(current, &cell)) {
+            loading.remove(key);
+        }
+        result
+    }
+
+    /// 删除键，返回删除的数量。锁和令牌桶只能通过各自的接口修改，删除它们返回 `WrongType`。
+    pub async fn delete(&self, key: &str) -> Result<i64, CacheError> {
+        // 已过期但尚未清理的条目视为不存在
+        self.update(key, |current| {
+            Ok(match current {
+                Some(_) => (Op::Remove, 1),
+                None => (Op::Nop, 0),
+            })
+        })
+        .await
+    }
+
+    /// 在同一个哈希槽的多个键上原子地执行一批操作，全部生效或全部不生效。
+    /// 先检查所有 Compare，满足后按顺序执行写入；某个写入失败时撤销之前的写入。
+    /// 返回每个操作的结果：Set 为新版本号，Delete 为删除的数量，Compare 为当前版本号。
+    /// 执行期间同一个槽上的其他读写都会等待它结束，撤销之前的写入也不会被看到。
+    pub async fn transaction(&self, ops: Vec<TxnOp>) -> Result<Vec<u64>, CacheError> {
+        let Some(first) = ops.first() else {
+            return Ok(Vec::new());
+        };
+        let slot = hash_slot(first.key());
+        if ops.iter().any(|op| hash_slot(op.key()) != slot) {
+            return Err(CacheError::CrossSlot);
+        }
+        let _slot = self.slot_lock(first.key()).write().await;
+
+        // 持有槽的写锁，检查之后这些键不会被其他请求修改
+        for op in &ops {
+            if let TxnOp::Compare { key, version } = op {
+                let current = self.live_version(key).await;
+                if current != *version {
+                    return Err(CacheError::VersionMismatch { expected: *version, current });
+                }
+            }
+        }
+
+        let mut results = Vec::with_capacity(ops.len());
+        let mut undo = Vec::new();
+        let mut batch = Vec::new();
+        for op in ops {
+            let result = match op {
+                TxnOp::Compare { version, .. } => Ok(version),
+                TxnOp::Set { key, value, ttl, tags } => {
+                    undo.push((key.clone(), self.store.get(&key).await));
+                    let entry = self.new_entry(&key, value, ttl, tags);
+                    self.apply_deferred(&key, true, false, Some(&mut batch), |_current| {
+                        let version = entry.version;
+                        Ok((Op::Put(entry), version))
+                    })
+                    .await
+                }
+                TxnOp::Delete { key } => {
+                    undo.push((key.clone(), self.store.get(&key).await));
+                    self.apply_deferred(&key, true, false, Some(&mut batch), |current| {
+                        Ok(match current {
+                            Some(_) => (Op::Remove, 1),
+                            None => (Op::Nop, 0),
+                        })
+                    })
+                    .await
+                }
+            };
+            match result {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    self.rollback(undo).await;
+                    return Err(e);
+                }
+            }
+        }
+        self.publish(batch).await;
+        Ok(results)
+    }
+
+    /// 按相反的顺序把事务写过的键恢复为事务之前的条目。
+    /// 失败的事务没有记入日志，也没有发布事件或进入写回队列，
+    /// 所以恢复原来的条目（连同原来的版本号）即可，重放时不会再出现事务的写入。
+    async fn rollback(&self, undo: Vec<(String, Option<CacheEntry>)>) {
+        let mut discarded = Vec::new();
+        for (key, previous) in undo.into_iter().rev() {
+            let restored = self
+                .apply_deferred(&key, true, false, Some(&mut discarded), |_current| {
+                    Ok((previous.map_or(Op::Remove, Op::Put), ()))
+                })
+                .await;
+            if let Err(e) = restored {
+                warn!("Failed to roll back key '{}': {}", key, e);
+            }
+        }
+    }
+
+    /// 事务全部成功后，把暂缓的写入作为一条记录写入日志，再发布事件、进入写回队列。
+    /// 调用方持有槽的写锁，同一个键的变更仍按生效的顺序送达。
+    async fn publish(&self, batch: Vec<Deferred>) {
+        if batch.is_empty() {
+            return;
+        }
+        let mut logged = Vec::new();
+        let mut fifo = Vec::new();
+        let queue = self.write_behind.get();
+        for deferred in batch {
+            let Deferred { key, logged: change, event, write_behind, put_version } = deferred;
+            if let Some((kind, version)) = event {
+                let _ = self.events.send(KeyEvent::new(kind, &key, version));
+            }
+            if let (Some(queue), Some(change)) = (queue, write_behind) {
+                match change {
+                    Some(value) => queue.enqueue_put(&key, &value),
+                    None => queue.enqueue_delete(&key),
+                }
+            }
+            if let Some(version) = put_version {
+                fifo.push((key.clone(), version));
+            }
+            logged.extend(change.map(|change| match change {
+                LoggedChange::Set(snapshot) => BatchOp::Set(snapshot),
+                LoggedChange::Delete(version) => BatchOp::Delete { key, version },
+            }));
+        }
+
+        if let Some(log) = self.log.get()
+            && !logged.is_empty()
+            && let Some(synced) = log.append_batch(logged)
+        {
+            let _ = synced.await;
+        }
+
+        if self.eviction_policy == EvictionPolicy::Fifo && !fifo.is_empty() {
+            self.fifo.lock().unwrap().extend(fifo);
+            self.evict_fifo().await;
+        }
+    }
+
+    /// 键当前存活条目的版本号，不存在时为 0
+    async fn live_version(&self, key: &str) -> u64 {
+        self.store
+            .get(key)
+            .await
+            .filter(|entry| !entry.is_expired(Instant::now()))
+            .map_or(0, |entry| entry.version)
+    }
+
+    /// 删除所有带有 `tag` 的条目，返回删除的数量。
+    /// 标签失效只作用于缓存，不删除后端存储中的值。
+    pub async fn invalidate_tag(&self, tag: &str) -> i64 {
+        let keys: Vec<Arc<String>> = self
+            .store
+            .iter()
+            .filter(|(_, entry)| entry.has_tag(tag))
+            .map(|(key, _)| key)
+            .collect();
+
+        let mut deleted = 0;
+        for key in keys {
+            // 扫描之后键可能已被覆盖为不带该标签的值，删除前再确认一次
+            deleted += self
+                .update_local(&key, |current| {
+                    Ok(match current {
+                        Some(entry) if entry.has_tag(tag) => (Op::Remove, 1),
+                        _ => (Op::Nop, 0),
+                    })
+                })
+                .await
+                .unwrap_or(0);
+        }
+        deleted
+    }
+
+    /// 按字典序列出以 `prefix` 开头且大于 `after` 的键，最多 `limit` 个。
+    /// 遍历基于 moka 的迭代器，不会锁住整个缓存。
+    pub fn keys(&self, prefix: &str, after: Option<&str>, limit: usize) -> Vec<String> {
+        let now = Instant::now();
+        let mut keys = BTreeSet::new();
+        for (key, entry) in self.store.iter().chain(self.managed.iter()) {
+            if !key.starts_with(prefix)
+                || after.is_some_and(|after| key.as_str() <= after)
+                || entry.is_expired(now)
+            {
+                continue;
+            }
+            keys.insert(key);
+            if keys.len() > limit {
+                keys.pop_last();
+            }
+        }
+        keys.into_iter().map(|key| key.to_string()).collect()
+    }
+
+    /// 导出所有存活的条目及其剩余 TTL，用于快照和重写日志。令牌桶不导出。
+    pub fn export(&self) -> Vec<EntrySnapshot> {
+        let now = Instant::now();
+        self.store
+            .iter()
+            .chain(self.managed.iter())
+            .filter(|(_, entry)| !entry.is_expired(now) && entry.value.is_durable())
+            .map(|(key, entry)| entry.snapshot(&key, now))
+            .collect()
+    }
+
+    /// 导入快照中的条目，返回导入的数量。
+    /// `elapsed` 是快照至今经过的时间，会从剩余 TTL 中扣除，已过期的条目被跳过。
+    /// 条目保留原有的版本号，之后的写入从更大的版本号继续；
+    /// 缓存中已有更新版本的键不会被覆盖。
+    pub async fn import(&self, entries: Vec<EntrySnapshot>, elapsed: Duration) -> usize {
+        let mut imported = 0;
+        for snapshot in entries {
+            // 已过期的条目也推进版本号，之后发出的版本号（包括锁的令牌）不会比它们小
+            self.next_version.fetch_max(snapshot.version + 1, Ordering::Relaxed);
+            let now = Instant::now();
+            let expires_at = match snapshot.ttl_ms.map(Duration::from_millis) {
+                Some(ttl) if ttl <= elapsed => continue,
+                Some(ttl) => Some(now + (ttl - elapsed)),
+                None => None,
+            };
+            let idle_timeout = snapshot.idle_ms.map(Duration::from_millis);
+            let Some(value) = CacheValue::from_snapshot(snapshot.value) else {
+                continue;
+            };
+
+            let entry = CacheEntry::new(
+                &snapshot.key,
+                value,
+                expires_at,
+                idle_timeout,
+                snapshot.version,
+                snapshot.tags.into(),
+            )
+            .with_grace(self.grace_for(&snapshot.key));
+            let managed = entry.value.is_managed();
+            let restore = |current: Option<&CacheEntry>| {
+                Ok(match current {
+                    Some(current) if current.version >= entry.version => (Op::Nop, false),
+                    _ => (Op::Put(entry), true),
+                })
+            };
+            let result = match managed {
+                true => self.update_managed(&snapshot.key, restore).await,
+                false => self.update_local(&snapshot.key, restore).await,
+            };
+            if let Ok(true) = result {
+                imported += 1;
+            }
+        }
+        imported
+    }
+
+    /// 删除版本号不大于 `version` 的条目，用于重放日志中的删除
+    pub async fn delete_version(&self, key: &str, version: u64) {
+        self.next_version.fetch_max(version + 1, Ordering::Relaxed);
+        let remove = |current: Option<&CacheEntry>| {
+            Ok(match current {
+                Some(current) if current.version <= version => (Op::Remove, ()),
+                _ => (Op::Nop, ()),
+            })
+        };
+        let _ = match self.managed.contains_key(key) {
+            true => self.update_managed(key, remove).await,
+            false => self.update_local(key, remove).await,
+        };
+    }
+
+    /// 下一个要发出的版本号。已发出的版本号都比它小，重写日志时据此保留版本号的高水位
+    pub fn version_high_water(&self) -> u64 {
+        self.next_version.load(Ordering::Relaxed)
+    }
+
+    /// 保证之后发出的版本号不小于 `next`，用于重放日志中的高水位
+    pub fn advance_version(&self, next: u64) {
+        self.next_version.fetch_max(next, Ordering::Relaxed);
+    }
+
+    /// 当前存活的条目数（近似值，随 moka 的维护任务更新）
+    pub fn entry_count(&self) -> u64 {
+        self.store.entry_count() + self.managed.entry_count()
+    }
+
+    /// 当前的加权大小：启用内存预算时为估算字节数，否则等于条目数
+    pub fn weighted_size(&self) -> u64 {
+        self.store.weighted_size()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entry_count: self.entry_count(),
+            weighted_size: self.weighted_size(),
+            max_capacity: self.max_capacity,
+            memory_budget: self.memory_budget,
+            eviction_policy: self.eviction_policy,
+        }
+    }
+
+    /// 执行 moka 挂起的维护任务，包括清理已过期的条目
+    pub async fn run_pending_tasks(&self) {
+        self.store.run_pending_tasks().await;
+        self.managed.run_pending_tasks().await;
+    }
+
+    /// 缓存写满时先清理过期条目，避免 LRU 驱逐仍然存活的键
+    async fn purge_expired_if_full(&self) {
+        if self.used.load(Ordering::Relaxed) >= self.max_capacity {
+            self.store.run_pending_tasks().await;
+        }
+    }
+
+    fn new_entry(&self, key: &str, value: CacheValue, ttl: EntryTtl, tags: Vec<String>) -> CacheEntry {
+        let (expires_at, idle_timeout) = self.expiry_for(self.jittered(ttl));
+        CacheEntry::new(key, value, expires_at, idle_timeout, self.next_version(), tags.into())
+            .with_grace(self.grace_for(key))
+    }
+
+    /// 从现在起按 `ttl` 过期的 (过期时刻, 空闲时长)
+    fn expiry_for(&self, ttl: CacheItemTTL) -> (Option<Instant>, Option<Duration>) {
+        match ttl {
+            CacheItemTTL::Default => (Some(Instant::now() + self.default_ttl), None),
+            CacheItemTTL::Permanent => (None, None),
+            CacheItemTTL::Custom(dur) => (Some(Instant::now() + dur), None),
+            CacheItemTTL::Idle(dur) => (None, Some(dur)),
+        }
+    }
+
+    /// 写入新值时给 Default 和 Custom 加上 TTL 抖动；修改已有键的过期设置时不加
+    fn jittered(&self, ttl: EntryTtl) -> CacheItemTTL {
+        match ttl.ttl {
+            CacheItemTTL::Default if ttl.jitter => CacheItemTTL::Custom(self.ttl_jitter.apply(self.default_ttl)),
+            CacheItemTTL::Custom(dur) if ttl.jitter => CacheItemTTL::Custom(self.ttl_jitter.apply(dur)),
+            ttl => ttl,
+        }
+    }
+
+    /// 键的宽限期，由匹配的回源配置决定
+    fn grace_for(&self, key: &str) -> Grace {
+        self.origins.find(key).map(Origin::grace).unwrap_or_default()
+    }
+
+    fn next_version(&self) -> u64 {
+        self.next_version.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// 在 moka 的键级锁内原子地执行读-改-写。
+    /// `f` 拿到当前存活的条目，返回要执行的操作以及调用方需要的结果；
+    /// 所有写入都经过这里，保证与 CAS 等条件写入互斥。
+    /// 客户端的写入和删除同时进入写回队列。
+    async fn update<T, F>(&self, key: &str, f: F) -> Result<T, CacheError>
+    where
+        F: FnOnce(Option<&CacheEntry>) -> Result<(Op<CacheEntry>, T), CacheError>,
+    {
+        self.apply(key, true, f).await
+    }
+
+    /// 同 `update`，但只改变缓存，不写回后端存储。
+    /// 用于标签失效、修改过期设置、快照与日志的恢复以及回源加载。
+    async fn update_local<T, F>(&self, key: &str, f: F) -> Result<T, CacheError>
+    where
+        F: FnOnce(Option<&CacheEntry>) -> Result<(Op<CacheEntry>, T), CacheError>,
+    {
+        self.apply(key, false, f).await
+    }
+
+    /// 修改锁或令牌桶。在普通存储的键级锁内执行，与普通写入互斥，
+    /// 同一个键不会同时出现在两个存储中；键名被普通的值占用时返回 `WrongType`。
+    async fn update_managed<T, F>(&self, key: &str, f: F) -> Result<T, CacheError>
+    where
+        F: FnOnce(Option<&CacheEntry>) -> Result<(Op<CacheEntry>, T), CacheError>,
+    {
+        let _slot = self.slot_lock(key).read().await;
+        let mut result = None;
+        let output = &mut result;
+        self.store
+            .entry_by_ref(key)
+            .and_compute_with(|maybe_entry| async move {
+                let occupied = maybe_entry.is_some_and(|entry| !entry.value().is_expired(Instant::now()));
+                *output = Some(match occupied {
+                    true => Err(CacheError::WrongType),
+                    false => self.apply_deferred(key, false, true, None, f).await,
+                });
+                Op::Nop
+            })
+            .await;
+        result.expect("compute closure is always invoked")
+    }
+
+    async fn apply<T, F>(&self, key: &str, write_behind: bool, f: F) -> Result<T, CacheError>
+    where
+        F: FnOnce(Option<&CacheEntry>) -> Result<(Op<CacheEntry>, T), CacheError>,
+    {
+        let _slot = self.slot_lock(key).read().await;
+        self.apply_locked(key, write_behind, f).await
+    }
+
+    /// 同 `apply`，调用方已持有键所在槽的锁
+    async fn apply_locked<T, F>(&self, key: &str, write_behind: bool, f: F) -> Result<T, CacheError>
+    where
+        F: FnOnce(Option<&CacheEntry>) -> Result<(Op<CacheEntry>, T), CacheError>,
+    {
+        self.apply_deferred(key, write_behind, false, None, f).await
+    }
+
+    /// 同 `apply_locked`。`managed` 为真时写入锁和令牌桶的存储，不计入容量。
+    /// `batch` 不为空时写入只改变缓存，
+    /// 日志、事件和写回队列的变更放进 `batch`，由事务在全部成功后统一发布。
+    async fn apply_deferred<T, F>(
+        &self,
+        key: &str,
+        write_behind: bool,
+        managed: bool,
+        batch: Option<&mut Vec<Deferred>>,
+        f: F,
+    ) -> Result<T, CacheError>
+    where
+        F: FnOnce(Option<&CacheEntry>) -> Result<(Op<CacheEntry>, T), CacheError>,
+    {
+        let store = match managed {
+            true => &self.managed,
+            false => {
+                self.purge_expired_if_full().await;
+                &self.store
+            }
+        };
+
+        let log = self.log.get();
+        let queue = self.write_behind.get().filter(|_| write_behind);
+        let mut output = None;
+        let mut put_version = None;
+        let mut logged = None;
+        let defer = batch.is_some();
+        let mut deferred = None;
+        store
+            .entry_by_ref(key)
+            .and_try_compute_with(|maybe_entry| {
+                // 宽限期内的条目已过期，对写入不可见
+                let stored = maybe_entry.as_ref().map(|e| e.value());
+                let current = stored.filter(|e| !e.is_expired(Instant::now()));
+                // 键名被锁或令牌桶占用时普通写入不生效
+                let checked = match !managed && self.managed.contains_key(key) {
+                    true => Err(CacheError::WrongType),
+                    false => Ok(()),
+                };
+                let result = checked.and_then(|()| f(current)).and_then(|(op, out)| {
+                    // 锁和令牌桶只能经由各自的接口写入锁的存储，不能混进普通存储
+                    if let Op::Put(entry) = &op
+                        && !managed
+                        && entry.value.is_managed()
+                    {
+                        return Err(CacheError::WrongType);
+                    }
+                    // 没有写入新值时顺带移除过期的条目
+                    let mut op = match op {
+                        Op::Nop if stored.is_some() && current.is_none() => Op::Remove,
+                        op => op,
+                    };
+                    if let (Op::Put(entry), Some(current)) = (&mut op, current) {
+                        entry.inherit(current);
+                    }
+                    if let Op::Put(entry) = &op
+                        && !managed
+                    {
+                        self.reserve(stored.is_some(), entry_weight(self.memory_budget, entry))?;
+                        put_version = Some(entry.version);
+                    }
+                    // 令牌桶的变更不记入日志，也不发布事件
+                    let durable = match (&op, stored) {
+                        (Op::Put(entry), _) => entry.value.is_durable(),
+                        (_, Some(stored)) => stored.value.is_durable(),
+                        (_, None) => true,
+                    };
+                    if log.is_some() && durable {
+                        logged = match (&op, current) {
+                            (Op::Put(entry), _) => {
+                                Some(LoggedChange::Set(entry.snapshot(key, Instant::now())))
+                            }
+                            (Op::Remove, Some(current)) => Some(LoggedChange::Delete(current.version)),
+                            _ => None,
+                        };
+                    }
+                    let event = match (&op, stored, current) {
+                        _ if !durable => None,
+                        (Op::Put(entry), _, _) => Some((KeyEventKind::Set, entry.version)),
+                        (Op::Remove, _, Some(current)) => Some((KeyEventKind::Delete, current.version)),
+                        (Op::Remove, Some(stored), None) => Some((KeyEventKind::Expire, stored.version)),
+                        _ => None,
+                    };
+                    if defer {
+                        deferred = Some((event, queue.and_then(|_| match (&op, current) {
+                            (Op::Put(entry), _) => Some(Some(entry.value.clone())),
+                            (Op::Remove, Some(_)) => Some(None),
+                            _ => None,
+                        })));
+                        output = Some(out);
+                        return Ok(op);
+                    }
+                    // 在键级锁内发布事件和入队，同一个键的变更按生效的顺序送达
+                    if let Some((kind, version)) = event {
+                        let _ = self.events.send(KeyEvent::new(kind, key, version));
+                    }
+                    if let Some(queue) = queue {
+                        match (&op, current) {
+                            (Op::Put(entry), _) => queue.enqueue_put(key, &entry.value),
+                            (Op::Remove, Some(_)) => queue.enqueue_delete(key),
+                            _ => {}
+                        }
+                    }
+                    output = Some(out);
+                    Ok(op)
+                });
+                std::future::ready(result)
+            })
+            .await?;
+
+        if let (Some(batch), Some((event, write_behind))) = (batch, deferred) {
+            batch.push(Deferred { key: key.to_string(), logged, event, write_behind, put_version });
+            return Ok(output.expect("compute closure is always invoked"));
+        }
+
+        // 写入生效之后才记入日志，日志重写导出的状态因此不会落后于日志
+        if let (Some(log), Some(logged)) = (log, logged) {
+            let synced = match logged {
+                LoggedChange::Set(snapshot) => log.append_set(snapshot),
+                LoggedChange::Delete(version) => log.append_delete(key, version),
+            };
+            if let Some(synced) = synced {
+                let _ = synced.await;
+            }
+        }
+
+        if let Some(version) = put_version
+            && self.eviction_policy == EvictionPolicy::Fifo
+        {
+            self.fifo.lock().unwrap().push_back((key.to_string(), version));
+            self.evict_fifo().await;
+        }
+        Ok(output.expect("compute closure is always invoked"))
+    }
+
+    fn slot_lock(&self, key: &str) -> &RwLock<()> {
+        let mut hasher = DefaultHasher::new();
+        hash_slot(key).hash(&mut hasher);
+        &self.slot_locks[hasher.finish() as usize % self.slot_locks.len()]
+    }
+
+    /// 为即将写入的条目计入容量。
+    /// None 策略下写满时拒绝新键（覆盖已有的键仍然允许）。
+    fn reserve(&self, exists: bool, weight: u32) -> Result<(), CacheError> {
+        let weight = weight as u64;
+        let used = self.used.fetch_add(weight, Ordering::Relaxed) + weight;
+        if self.eviction_policy != EvictionPolicy::None || used <= self.max_capacity || exists {
+            return Ok(());
+        }
+        self.used.fetch_sub(weight, Ordering::Relaxed);
+        Err(CacheError::CacheFull)
+    }
+
+    /// FIFO 策略：按写入顺序驱逐最早的条目，直到不超过容量上限
+    async fn evict_fifo(&self) {
+        while self.used.load(Ordering::Relaxed) > self.max_capacity {
+            let Some((key, version)) = self.fifo.lock().unwrap().pop_front() else {
+                break;
+            };
+            // 键在入队之后可能已被覆盖写入，只删除同一次写入产生的条目
+            let result = self
+                .store
+                .entry_by_ref(&key)
+                .and_compute_with(|maybe_entry| async move {
+                    match maybe_entry {
+                        Some(entry) if entry.value().version == version => Op::Remove,
+                        _ => Op::Nop,
+                    }
+                })
+                .await;
+            if !matches!(result, CompResult::Removed(_)) {
+                continue;
+            }
+            let _ = self.events.send(KeyEvent::new(KeyEventKind::Evict, &key, version));
+            if let Some(log) = self.log.get()
+                && let Some(synced) = log.append_delete(&key, version)
+            {
+                let _ = synced.await;
+            }
+        }
+        self.compact_fifo();
+    }
+
+    /// 清理队列中已被删除、过期或覆盖的记录，避免队列无限增长
+    fn compact_fifo(&self) {
+        let live = self.store.entry_count() as usize;
+        let mut fifo = self.fifo.lock().unwrap();
+        if fifo.len() <= (live * 2).max(1024) {
+            return;
+        }
+        let current: HashMap<Arc<String>, u64> =
+            self.store.iter().map(|(key, entry)| (key, entry.version)).collect();
+        fifo.retain(|(key, version)| current.get(key) == Some(version));
+    }
+}
+
+
+// --- 单元测试 ---
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn test_settings(capacity: u64, default_ttl_sec: u64) -> CacheSettings {
+        CacheSettings {
+            capacity,
+            default_ttl_seconds: default_ttl_sec,
+            ..CacheSettings::for_tests()
+        }
+    }
+
+    fn create_test_cache(capacity: u64, default_ttl_sec: u64) -> CacheStore {
+        CacheStore::new(&test_settings(capacity, default_ttl_sec))
+    }
+    
+    #[tokio::test]
+    async fn test_lru_eviction_still_works() {
+        let cache = create_test_cache(2, 60); // 容量为 2
+
+        // 插入 2 个永久条目，然后访问 key1，使 key2 成为最久未使用的条目
+        cache.set("key1".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.store.run_pending_tasks().await;
+        cache.set("key2".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.store.run_pending_tasks().await;
+        assert_eq!(cache.get("key1").await, Some(json!(1)));
+        cache.store.run_pending_tasks().await;
+        cache.set("key3".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        cache.store.run_pending_tasks().await;
+
+        let count = cache.store.entry_count();
+        println!("当前缓存条目数: {}", count);
+
+        // cache.store.run_pending_tasks().await;
+
+        assert_eq!(cache.get("key2").await, None, "key2 应该被 LRU 驱逐");
+        assert_eq!(cache.get("key1").await, Some(json!(1)));
+        assert_eq!(cache.get("key3").await, Some(json!(3)));
+    }
+
+    fn create_policy_cache(capacity: u64, eviction_policy: EvictionPolicy) -> CacheStore {
+        CacheStore::new(&CacheSettings {
+            eviction_policy,
+            ..test_settings(capacity, 60)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tiny_lfu_rejects_infrequent_newcomer() {
+        let cache = create_policy_cache(2, EvictionPolicy::TinyLfu);
+
+        cache.set("hot1".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("hot2".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+        for _ in 0..5 {
+            cache.get("hot1").await;
+            cache.get("hot2").await;
+        }
+        cache.run_pending_tasks().await;
+
+        // 新键的访问频率低于任何现有条目，TinyLFU 拒绝接纳它
+        cache.set("cold".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+
+        assert_eq!(cache.get("cold").await, None);
+        assert_eq!(cache.get("hot1").await, Some(json!(1)));
+        assert_eq!(cache.get("hot2").await, Some(json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_fifo_evicts_oldest_write_regardless_of_reads() {
+        let cache = create_policy_cache(2, EvictionPolicy::Fifo);
+
+        cache.set("key1".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("key2".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert_eq!(cache.get("key1").await, Some(json!(1)));
+        cache.set("key3".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+
+        assert_eq!(cache.get("key1").await, None, "key1 最先写入，应该被 FIFO 驱逐");
+        assert_eq!(cache.get("key2").await, Some(json!(2)));
+        assert_eq!(cache.get("key3").await, Some(json!(3)));
+        assert_eq!(cache.entry_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fifo_overwrite_moves_key_to_back() {
+        let cache = create_policy_cache(2, EvictionPolicy::Fifo);
+
+        cache.set("key1".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("key2".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("key1".to_string(), json!(10), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("key3".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+
+        assert_eq!(cache.get("key2").await, None);
+        assert_eq!(cache.get("key1").await, Some(json!(10)));
+        assert_eq!(cache.get("key3").await, Some(json!(3)));
+    }
+
+    #[tokio::test]
+    async fn test_none_policy_rejects_new_keys_when_full() {
+        let cache = create_policy_cache(2, EvictionPolicy::None);
+
+        cache.set("key1".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("key2".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        let result = cache.set("key3".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await;
+        assert!(matches!(result, Err(CacheError::CacheFull)));
+
+        // 已有的键仍然可以覆盖
+        cache.set("key1".to_string(), json!(10), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert_eq!(cache.get("key1").await, Some(json!(10)));
+        assert_eq!(cache.get("key2").await, Some(json!(2)));
+
+        // 删除后腾出空间
+        assert_eq!(cache.delete("key2").await.unwrap(), 1);
+        cache.set("key3".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert_eq!(cache.get("key3").await, Some(json!(3)));
+    }
+
+    #[tokio::test]
+    async fn test_stats_report_eviction_policy() {
+        let cache = create_policy_cache(2, EvictionPolicy::Fifo);
+        assert_eq!(cache.stats().eviction_policy, EvictionPolicy::Fifo);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_not_counted() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("short".to_string(), json!(1), CacheItemTTL::Custom(Duration::from_millis(100)), vec![]).await.unwrap();
+        cache.set("long".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+        assert_eq!(cache.entry_count(), 2);
+
+        // moka 的时间轮精度约为 1 秒
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        cache.run_pending_tasks().await;
+
+        // 没有任何人读取 "short"，它也应当已经被清理
+        assert_eq!(cache.entry_count(), 1);
+        assert_eq!(cache.get("short").await, None);
+        assert_eq!(cache.get("long").await, Some(json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_evicted_before_live_ones() {
+        let cache = create_test_cache(2, 60);
+
+        cache.set("expiring".to_string(), json!(1), CacheItemTTL::Custom(Duration::from_millis(100)), vec![]).await.unwrap();
+        cache.set("live".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+
+        // moka 的时间轮精度约为 1 秒
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        cache.set("new".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+
+        assert_eq!(cache.get("live").await, Some(json!(2)), "存活的键不应被驱逐");
+        assert_eq!(cache.get("new").await, Some(json!(3)));
+        assert_eq!(cache.entry_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_delete_expired_key_counts_zero() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("key".to_string(), json!(1), CacheItemTTL::Custom(Duration::from_millis(10)), vec![]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        assert_eq!(cache.delete("key").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_memory_budget_evicts_by_size() {
+        let settings = CacheSettings {
+            max_memory_bytes: Some(1024),
+            ..test_settings(10000, 60)
+        };
+        let cache = CacheStore::new(&settings);
+
+        let big = json!("x".repeat(400));
+        for i in 0..5 {
+            cache.set(format!("doc{}", i), big.clone(), CacheItemTTL::Permanent, vec![]).await.unwrap();
+            cache.run_pending_tasks().await;
+        }
+
+        // 每个条目约 400 字节，1KB 的预算最多容纳两个
+        let stats = cache.stats();
+        assert!(stats.memory_budget);
+        assert!(stats.entry_count <= 2, "entry_count = {}", stats.entry_count);
+        assert!(stats.weighted_size <= 1024, "weighted_size = {}", stats.weighted_size);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_size_tracks_entry_size() {
+        let settings = CacheSettings {
+            max_memory_bytes: Some(1024 * 1024),
+            ..test_settings(10000, 60)
+        };
+        let cache = CacheStore::new(&settings);
+
+        cache.set("k".to_string(), json!({"a": 1}), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.run_pending_tasks().await;
+
+        // "k" + {"a":1}
+        assert_eq!(cache.weighted_size(), 1 + 7);
+    }
+
+    #[tokio::test]
+    async fn test_idle_ttl_extended_by_reads() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("session".to_string(), json!("s"), CacheItemTTL::Idle(Duration::from_millis(300)), vec![]).await.unwrap();
+
+        // 每次读取都在空闲时长内，条目的总寿命可以超过空闲时长
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(cache.get("session").await, Some(json!("s")));
+        }
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(cache.get("session").await, None);
+        assert_eq!(cache.delete("session").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_entry_meta() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("k".to_string(), json!("v1"), CacheItemTTL::Custom(Duration::from_secs(30)), vec!["t".to_string()]).await.unwrap();
+        let created = cache.meta("k").await.unwrap();
+        assert_eq!(created.ttl_mode, TtlMode::Fixed);
+        assert!(created.remaining_ttl_ms.unwrap() > 29_000);
+        assert_eq!((created.access_count, created.last_access_ms), (0, None));
+        assert_eq!(created.tags, vec!["t".to_string()]);
+
+        cache.get("k").await;
+        cache.get("k").await;
+        // 覆盖写入沿用创建时间和访问统计
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let version = cache.set("k".to_string(), json!("v2"), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let meta = cache.meta("k").await.unwrap();
+        assert_eq!(meta.access_count, 2);
+        assert!(meta.last_access_ms.is_some());
+        assert_eq!(meta.created_at_ms, created.created_at_ms);
+        assert!(meta.updated_at_ms > created.updated_at_ms);
+        assert_eq!((meta.ttl_mode, meta.remaining_ttl_ms), (TtlMode::Permanent, None));
+        assert_eq!(meta.version, version);
+
+        assert!(cache.meta("missing").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_meta_does_not_extend_idle_ttl() {
+        let cache = create_test_cache(10, 60);
+        cache.set("session".to_string(), json!("s"), CacheItemTTL::Idle(Duration::from_millis(300)), vec![]).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let meta = cache.meta("session").await.unwrap();
+        assert_eq!(meta.ttl_mode, TtlMode::Idle);
+        assert!(meta.remaining_ttl_ms.unwrap() <= 100);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(cache.get("session").await, None);
+        assert_eq!(cache.meta("session").await.map(|m| m.access_count), None);
+    }
+
+    #[tokio::test]
+    async fn test_expire_and_persist() {
+        let cache = create_test_cache(10, 60);
+        let v1 = cache.set("k".to_string(), json!("v"), CacheItemTTL::Custom(Duration::from_secs(30)), vec!["t".to_string()]).await.unwrap();
+
+        let v2 = cache.expire("k", CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(v2, v1);
+        assert_eq!(cache.ttl("k").await, Some((TtlMode::Permanent, None)));
+
+        // 只改变过期设置，值和标签保持不变
+        let v3 = cache.expire("k", CacheItemTTL::Custom(Duration::from_millis(200))).await.unwrap();
+        assert_eq!(v3, v1);
+        let (mode, remaining) = cache.ttl("k").await.unwrap();
+        assert_eq!(mode, TtlMode::Fixed);
+        assert!(remaining.unwrap() <= Duration::from_millis(200));
+        assert_eq!(cache.get_versioned("k").await, Some((json!("v").into(), v3)));
+        assert_eq!(cache.meta("k").await.unwrap().tags, vec!["t".to_string()]);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(cache.ttl("k").await, None);
+        assert!(matches!(
+            cache.expire("k", CacheItemTTL::Permanent).await,
+            Err(CacheError::KeyNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ttl_jitter() {
+        let cache = CacheStore::new(&CacheSettings {
+            ttl_jitter_percent: Some(50),
+            ..test_settings(100, 100)
+        });
+
+        // 抖动只延长 TTL：默认和指定的 TTL 落在 [ttl, ttl * 1.5] 之间，且不会全都相同
+        let mut remaining = BTreeSet::new();
+        for i in 0..20 {
+            let ttl = if i % 2 == 0 { CacheItemTTL::Default } else { CacheItemTTL::Custom(Duration::from_secs(100)) };
+            cache.set(format!("k{}", i), json!(i), ttl, vec![]).await.unwrap();
+            let meta = cache.meta(&format!("k{}", i)).await.unwrap();
+            let ms = meta.remaining_ttl_ms.unwrap();
+            assert!((99_000..=150_000).contains(&ms), "remaining_ttl_ms = {}", ms);
+            assert!(meta.expires_at_ms.unwrap() >= meta.updated_at_ms + ms);
+            remaining.insert(ms / 1000);
+        }
+        assert!(remaining.len() > 1);
+
+        // 关闭抖动后精确按请求的 TTL 过期
+        cache.set("exact".to_string(), json!(1), EntryTtl { ttl: CacheItemTTL::Custom(Duration::from_secs(100)), jitter: false }, vec![]).await.unwrap();
+        assert!(cache.meta("exact").await.unwrap().remaining_ttl_ms.unwrap() <= 100_000);
+        cache.set("exact".to_string(), json!(1), EntryTtl { ttl: CacheItemTTL::Default, jitter: false }, vec![]).await.unwrap();
+        assert!(cache.meta("exact").await.unwrap().remaining_ttl_ms.unwrap() <= 100_000);
+
+        // 修改已有键的过期设置不加抖动
+        cache.expire("k0", CacheItemTTL::Custom(Duration::from_secs(10))).await.unwrap();
+        assert!(cache.meta("k0").await.unwrap().remaining_ttl_ms.unwrap() <= 10_000);
+
+        // 极大的 TTL 计算抖动时饱和而不是溢出
+        let jitter = TtlJitter { percent: 100, absolute: Duration::ZERO };
+        assert_eq!(jitter.apply(Duration::MAX), Duration::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_hash_fields() {
+        let cache = create_test_cache(10, 60);
+        let ttl = CacheItemTTL::Custom(Duration::from_secs(30));
+
+        let (created, v1) = cache.hash_set("user".to_string(), "name".to_string(), json!("alice"), ttl).await.unwrap();
+        assert!(created);
+        let (created, _) = cache.hash_set("user".to_string(), "age".to_string(), json!(30), CacheItemTTL::Permanent).await.unwrap();
+        assert!(created);
+        let (created, v3) = cache.hash_set("user".to_string(), "age".to_string(), json!(31), CacheItemTTL::Permanent).await.unwrap();
+        assert!(!created);
+        assert!(v3 > v1);
+
+        assert_eq!(cache.hash_get("user", "age").await.unwrap(), Some(json!(31)));
+        assert_eq!(cache.hash_get("user", "email").await.unwrap(), None);
+        assert_eq!(cache.len("user").await.unwrap(), 2);
+        assert_eq!(
+            cache.hash_get_all("user").await.unwrap(),
+            Some(HashFields::from([("age".to_string(), json!(31)), ("name".to_string(), json!("alice"))]))
+        );
+        // 已存在的哈希保留创建时的过期设置
+        assert_eq!(cache.ttl("user").await.unwrap().0, TtlMode::Fixed);
+
+        // 哈希在快照中保留类型
+        let snapshot = CacheValue::Hash(cache.hash_get_all("user").await.unwrap().unwrap()).to_snapshot();
+        assert_eq!(snapshot.data_type.as_deref(), Some("hash"));
+        assert!(matches!(CacheValue::from_snapshot(snapshot), Some(CacheValue::Hash(fields)) if fields.len() == 2));
+
+        // 内容类型与数据结构的类型名分开保存，看起来像类型名的内容类型仍是二进制值
+        let binary = CacheValue::Binary { content_type: "@hash".to_string(), data: Bytes::from_static(b"{}") };
+        assert_eq!(CacheValue::from_snapshot(binary.to_snapshot()), Some(binary));
+
+        // 对其他类型的值执行哈希操作
+        cache.set("plain".to_string(), json!({"name": "bob"}), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(matches!(cache.hash_get("plain", "name").await, Err(CacheError::WrongType)));
+        assert!(matches!(
+            cache.hash_set("plain".to_string(), "name".to_string(), json!("x"), CacheItemTTL::Permanent).await,
+            Err(CacheError::WrongType)
+        ));
+
+        // 删除最后一个字段时键也被删除
+        assert!(cache.hash_delete("user", "name").await.unwrap());
+        assert!(!cache.hash_delete("user", "name").await.unwrap());
+        assert!(cache.hash_delete("user", "age").await.unwrap());
+        assert_eq!(cache.hash_get_all("user").await.unwrap(), None);
+        assert_eq!(cache.len("user").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_list_operations() {
+        let cache = create_test_cache(10, 60);
+        let push = |values: Vec<Value>, end| cache.list_push("feed".to_string(), values, end, CacheItemTTL::Permanent);
+
+        assert_eq!(push(vec![json!(1), json!(2)], ListEnd::Back).await.unwrap().0, 2);
+        // 从头部压入时结果与给定的顺序相反
+        assert_eq!(push(vec![json!(0), json!(-1)], ListEnd::Front).await.unwrap().0, 4);
+        assert_eq!(cache.list_range("feed", 0, -1).await.unwrap(), vec![json!(-1), json!(0), json!(1), json!(2)]);
+        assert_eq!(cache.list_range("feed", -2, 10).await.unwrap(), vec![json!(1), json!(2)]);
+        assert!(cache.list_range("feed", 3, 1).await.unwrap().is_empty());
+
+        assert_eq!(cache.list_pop("feed", ListEnd::Back, 1).await.unwrap(), vec![json!(2)]);
+        assert_eq!(cache.list_trim("feed", 0, 1).await.unwrap(), 2);
+        assert_eq!(cache.list_range("feed", 0, -1).await.unwrap(), vec![json!(-1), json!(0)]);
+        assert_eq!(cache.len("feed").await.unwrap(), 2);
+
+        // 弹出最后的值时键也被删除
+        assert_eq!(cache.list_pop("feed", ListEnd::Front, 5).await.unwrap(), vec![json!(-1), json!(0)]);
+        assert_eq!(cache.meta("feed").await.map(|m| m.version), None);
+        assert!(cache.list_pop("feed", ListEnd::Front, 1).await.unwrap().is_empty());
+
+        cache.set("plain".to_string(), json!([1, 2]), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(matches!(cache.list_range("plain", 0, -1).await, Err(CacheError::WrongType)));
+        assert!(matches!(cache.len("plain").await, Err(CacheError::WrongType)));
+        // 元素个数有上限，超限的写入不生效
+        let values: Vec<Value> = (0..MAX_COLLECTION_LEN).map(|i| json!(i)).collect();
+        cache.list_push("big".to_string(), values, ListEnd::Back, CacheItemTTL::Permanent).await.unwrap();
+        assert!(matches!(
+            cache.list_push("big".to_string(), vec![json!(0)], ListEnd::Back, CacheItemTTL::Permanent).await,
+            Err(CacheError::CollectionTooLarge)
+        ));
+        assert_eq!(cache.len("big").await.unwrap(), MAX_COLLECTION_LEN as u64);
+        assert_eq!(cache.list_pop("big", ListEnd::Front, 1).await.unwrap(), vec![json!(0)]);
+    }
+
+    #[tokio::test]
+    async fn test_set_operations() {
+        let cache = create_test_cache(10, 60);
+        let members = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let (added, v1) = cache.set_add("team".to_string(), members(&["a", "b"]), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(added, 2);
+        // 没有新成员时不写入，版本号不变
+        assert_eq!(cache.set_add("team".to_string(), members(&["a"]), CacheItemTTL::Permanent).await.unwrap(), (0, v1));
+        assert_eq!(cache.set_add("team".to_string(), members(&["c", "a"]), CacheItemTTL::Permanent).await.unwrap().0, 1);
+
+        assert!(cache.set_contains("team", "c").await.unwrap());
+        assert!(!cache.set_contains("team", "z").await.unwrap());
+        assert!(!cache.set_contains("missing", "a").await.unwrap());
+        assert_eq!(cache.set_members("team").await.unwrap(), members(&["a", "b", "c"]));
+
+        assert_eq!(cache.set_remove("team", &members(&["a", "z"])).await.unwrap(), 1);
+        assert_eq!(cache.set_remove("team", &members(&["b", "c"])).await.unwrap(), 2);
+        assert!(cache.set_members("team").await.unwrap().is_empty());
+        assert_eq!(cache.len("team").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sorted_set_operations() {
+        let cache = create_test_cache(10, 60);
+        let scores = |entries: &[(&str, f64)]| entries.iter().map(|(m, s)| (m.to_string(), *s)).collect::<Vec<_>>();
+
+        let added = cache
+            .zset_add("board".to_string(), scores(&[("alice", 30.0), ("bob", 10.0), ("carol", 20.0)]), CacheItemTTL::Permanent)
+            .await
+            .unwrap()
+            .0;
+        assert_eq!(added, 3);
+        // 更新分数不算新加入
+        assert_eq!(cache.zset_add("board".to_string(), scores(&[("bob", 50.0)]), CacheItemTTL::Permanent).await.unwrap().0, 0);
+
+        assert_eq!(cache.zset_rank("board", "bob", true).await.unwrap(), Some((0, 50.0)));
+        assert_eq!(cache.zset_rank("board", "bob", false).await.unwrap(), Some((2, 50.0)));
+        assert_eq!(cache.zset_rank("board", "dave", false).await.unwrap(), None);
+
+        assert_eq!(
+            cache.zset_range("board", ZsetRange::Rank { start: 0, stop: 1 }, true).await.unwrap(),
+            scores(&[("bob", 50.0), ("alice", 30.0)])
+        );
+        assert_eq!(
+            cache.zset_range("board", ZsetRange::Score { min: 15.0, max: 30.0 }, false).await.unwrap(),
+            scores(&[("carol", 20.0), ("alice", 30.0)])
+        );
+
+        // 有序集合在快照中保留类型和分数
+        let snapshot = cache.store.get("board").await.unwrap().value.to_snapshot();
+        assert_eq!(snapshot.data_type.as_deref(), Some("zset"));
+        let CacheValue::SortedSet(restored) = CacheValue::from_snapshot(snapshot).unwrap() else {
+            panic!("expected a sorted set");
+        };
+        assert_eq!(restored.rank("bob", true), Some((0, 50.0)));
+
+        assert_eq!(cache.zset_remove("board", &["bob".to_string(), "dave".to_string()]).await.unwrap(), 1);
+        assert_eq!(cache.len("board").await.unwrap(), 2);
+        assert!(matches!(
+            cache.set_add("board".to_string(), vec!["x".to_string()], CacheItemTTL::Permanent).await,
+            Err(CacheError::WrongType)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_key_events() {
+        let cache = create_policy_cache(1, EvictionPolicy::Fifo);
+        let mut events = cache.subscribe();
+
+        cache.set("a".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.delete("a").await.unwrap();
+        cache.set("b".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        // 容量为 1，写入 c 会驱逐 b
+        cache.set("c".to_string(), json!(3), CacheItemTTL::Custom(Duration::from_millis(100)), vec![]).await.unwrap();
+        // 过期的条目由 moka 清理时发布 expire，moka 的时间轮精度约为 1 秒
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        cache.run_pending_tasks().await;
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push((event.kind, event.key));
+        }
+        let expected = [
+            (KeyEventKind::Set, "a"),
+            (KeyEventKind::Delete, "a"),
+            (KeyEventKind::Set, "b"),
+            (KeyEventKind::Set, "c"),
+            (KeyEventKind::Evict, "b"),
+            (KeyEventKind::Expire, "c"),
+        ];
+        assert_eq!(received, expected.map(|(kind, key)| (kind, key.to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let cache = create_test_cache(10, 60);
+        let set = |key: &str, value: Value| TxnOp::Set {
+            key: key.to_string(),
+            value: CacheValue::Json(value),
+            ttl: CacheItemTTL::Permanent.into(),
+            tags: vec![],
+        };
+
+        let profile = cache.set("{u1}:profile".to_string(), json!("old"), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("{u1}:session".to_string(), json!("s1"), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        // 版本不匹配时整批不生效
+        let stale = vec![
+            set("{u1}:profile", json!("new")),
+            TxnOp::Compare { key: "{u1}:profile".to_string(), version: profile + 100 },
+        ];
+        assert!(matches!(cache.transaction(stale).await, Err(CacheError::VersionMismatch { .. })));
+        assert_eq!(cache.get("{u1}:profile").await, Some(json!("old")));
+
+        let results = cache
+            .transaction(vec![
+                TxnOp::Compare { key: "{u1}:profile".to_string(), version: profile },
+                TxnOp::Compare { key: "{u1}:missing".to_string(), version: 0 },
+                set("{u1}:profile", json!("new")),
+                TxnOp::Delete { key: "{u1}:session".to_string() },
+                TxnOp::Delete { key: "{u1}:missing".to_string() },
+            ])
+            .await
+            .unwrap();
+        assert_eq!(results[..2], [profile, 0]);
+        assert!(results[2] > profile);
+        assert_eq!(results[3..], [1, 0]);
+        assert_eq!(cache.get("{u1}:profile").await, Some(json!("new")));
+        assert_eq!(cache.get("{u1}:session").await, None);
+
+        // 不同槽的键不能放在同一个事务里
+        let cross = vec![set("{u1}:profile", json!(1)), set("{u2}:profile", json!(2))];
+        assert!(matches!(cache.transaction(cross).await, Err(CacheError::CrossSlot)));
+        assert_eq!(cache.get("{u2}:profile").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back_failed_write() {
+        let cache = create_policy_cache(2, EvictionPolicy::None);
+        let a = cache.set("{u}:a".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.set("{u}:b".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        // 覆盖 a 成功之后写入新键 c 时缓存已满，a 恢复原值
+        let ops = ["{u}:a", "{u}:c"]
+            .map(|key| TxnOp::Set {
+                key: key.to_string(),
+                value: CacheValue::Json(json!(10)),
+                ttl: CacheItemTTL::Permanent.into(),
+                tags: vec![],
+            })
+            .to_vec();
+        let mut events = cache.subscribe();
+        assert!(matches!(cache.transaction(ops).await, Err(CacheError::CacheFull)));
+        assert_eq!(cache.get_versioned("{u}:a").await, Some((CacheValue::Json(json!(1)), a)));
+        assert_eq!(cache.get("{u}:c").await, None);
+        // 失败的事务对外不可见
+        assert!(events.try_recv().is_err());
+
+        // 事务持有槽的写锁期间，同一个槽上的读取等待它结束
+        let slot = cache.slot_lock("{u}:a").write().await;
+        let reader = tokio::spawn({
+            let cache = cache.clone();
+            async move { cache.get("{u}:b").await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!reader.is_finished());
+        drop(slot);
+        assert_eq!(reader.await.unwrap(), Some(json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_lock_with_fencing_tokens() {
+        let cache = create_test_cache(10, 60);
+        let lease = Duration::from_secs(10);
+
+        // 租约为 0 的锁一获取就已过期，无论从哪个入口都拒绝
+        assert!(matches!(
+            cache.lock_acquire("job".to_string(), "a".to_string(), Duration::ZERO).await,
+            Err(CacheError::ZeroLease)
+        ));
+        let first = cache.lock_acquire("job".to_string(), "a".to_string(), lease).await.unwrap();
+        assert!(matches!(cache.lock_renew("job", first, Duration::ZERO).await, Err(CacheError::ZeroLease)));
+        assert!(matches!(
+            cache.lock_acquire("job".to_string(), "b".to_string(), lease).await,
+            Err(CacheError::LockHeld(holder)) if holder == "a"
+        ));
+        let (lock, remaining) = cache.lock_info("job").await.unwrap().unwrap();
+        assert_eq!(lock, LockState { holder: "a".to_string(), token: first });
+        assert!(remaining <= lease);
+
+        // 只有当前持有者的令牌能续租和释放，续租不改变令牌
+        assert!(matches!(cache.lock_renew("job", first + 1, lease).await, Err(CacheError::NotLockHolder)));
+        cache.lock_renew("job", first, lease).await.unwrap();
+        assert_eq!(cache.lock_info("job").await.unwrap().unwrap().0.token, first);
+        assert!(matches!(cache.lock_release("job", first + 1).await, Err(CacheError::NotLockHolder)));
+        cache.lock_release("job", first).await.unwrap();
+        assert_eq!(cache.lock_info("job").await.unwrap(), None);
+
+        // 租约到期后其他人可以获取，令牌更大，旧令牌失效
+        let second = cache.lock_acquire("job".to_string(), "a".to_string(), Duration::from_millis(20)).await.unwrap();
+        assert!(second > first);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        let third = cache.lock_acquire("job".to_string(), "b".to_string(), lease).await.unwrap();
+        assert!(third > second);
+        assert!(matches!(cache.lock_renew("job", second, lease).await, Err(CacheError::NotLockHolder)));
+
+        // 锁随快照保存和恢复
+        let snapshot = CacheValue::Lock(LockState { holder: "b".to_string(), token: third }).to_snapshot();
+        assert_eq!(snapshot.data_type.as_deref(), Some("lock"));
+        assert!(matches!(CacheValue::from_snapshot(snapshot), Some(CacheValue::Lock(lock)) if lock.token == third));
+
+        cache.set("plain".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(matches!(
+            cache.lock_acquire("plain".to_string(), "a".to_string(), lease).await,
+            Err(CacheError::WrongType)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lock_rejects_generic_writes() {
+        let cache = create_test_cache(10, 60);
+        let token = cache.lock_acquire("job".to_string(), "a".to_string(), Duration::from_secs(10)).await.unwrap();
+
+        // 普通的写入、删除和修改过期设置都不能改动锁
+        let wrong_type = |result: Result<u64, CacheError>| matches!(result, Err(CacheError::WrongType));
+        assert!(wrong_type(cache.set("job".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await));
+        assert!(wrong_type(cache.compare_and_set("job".to_string(), json!(1), CacheItemTTL::Permanent, vec![], token).await));
+        assert!(wrong_type(cache.expire("job", CacheItemTTL::Permanent).await));
+        assert!(matches!(cache.delete("job").await, Err(CacheError::WrongType)));
+        let txn = vec![TxnOp::Delete { key: "job".to_string() }];
+        assert!(matches!(cache.transaction(txn).await, Err(CacheError::WrongType)));
+        let txn = vec![TxnOp::Set {
+            key: "job".to_string(),
+            value: CacheValue::Json(json!(1)),
+            ttl: CacheItemTTL::Permanent.into(),
+            tags: vec![],
+        }];
+        assert!(matches!(cache.transaction(txn).await, Err(CacheError::WrongType)));
+
+        let (lock, remaining) = cache.lock_info("job").await.unwrap().unwrap();
+        assert_eq!(lock.token, token);
+        assert!(remaining > Duration::ZERO);
+        assert_eq!(cache.keys("", None, 10), vec!["job".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_locks_and_buckets_are_not_evicted() {
+        let limit = RateLimit::new(2, Duration::from_secs(10), 1).unwrap();
+        for policy in [EvictionPolicy::Lru, EvictionPolicy::TinyLfu, EvictionPolicy::Fifo, EvictionPolicy::None] {
+            let cache = create_policy_cache(2, policy);
+            let lease = Duration::from_secs(10);
+            let token = cache.lock_acquire("job".to_string(), "a".to_string(), lease).await.unwrap();
+            cache.rate_limit("api".to_string(), limit).await.unwrap();
+
+            // 锁和令牌桶不占容量，写满缓存也不会挤掉它们
+            for i in 0..2 {
+                cache.set(format!("k{}", i), json!(i), CacheItemTTL::Permanent, vec![]).await.unwrap();
+            }
+            for i in 2..10 {
+                let _ = cache.set(format!("k{}", i), json!(i), CacheItemTTL::Permanent, vec![]).await;
+            }
+            cache.run_pending_tasks().await;
+            assert_eq!(cache.lock_info("job").await.unwrap().unwrap().0.token, token, "{:?}", policy);
+            assert!(matches!(
+                cache.lock_acquire("job".to_string(), "b".to_string(), lease).await,
+                Err(CacheError::LockHeld(_))
+            ));
+            assert_eq!(cache.rate_limit("api".to_string(), limit).await.unwrap().remaining, 0, "{:?}", policy);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_token_bucket() {
+        let cache = create_test_cache(10, 60);
+        // 每 200 毫秒补满 2 个令牌，即每 100 毫秒一个
+        let limit = RateLimit::new(2, Duration::from_millis(200), 1).unwrap();
+
+        let mut events = cache.subscribe();
+        let first = cache.rate_limit("api".to_string(), limit).await.unwrap();
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        let (_, version) = cache.get_versioned("api").await.unwrap();
+        assert!(cache.rate_limit("api".to_string(), limit).await.unwrap().allowed);
+        // 桶的更新沿用版本号，不发布事件，也不随快照导出
+        assert_eq!(cache.get_versioned("api").await.unwrap().1, version);
+        assert!(events.try_recv().is_err());
+        assert!(cache.export().is_empty());
+
+        // 普通的写入和删除不能重置限流
+        assert!(matches!(
+            cache.set("api".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await,
+            Err(CacheError::WrongType)
+        ));
+        assert!(matches!(cache.delete("api").await, Err(CacheError::WrongType)));
+
+        let denied = cache.rate_limit("api".to_string(), limit).await.unwrap();
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert!(denied.retry_after > Duration::ZERO && denied.retry_after <= Duration::from_millis(100));
+
+        tokio::time::sleep(denied.retry_after + Duration::from_millis(10)).await;
+        assert!(cache.rate_limit("api".to_string(), limit).await.unwrap().allowed);
+
+        // 桶补满后条目过期，下次检查时重新从满的桶开始
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(cache.get("api").await, None);
+        assert_eq!(cache.rate_limit("api".to_string(), limit).await.unwrap().remaining, 1);
+
+        assert!(RateLimit::new(1, Duration::from_secs(1), 2).is_err());
+        assert!(RateLimit::new(0, Duration::from_secs(1), 0).is_err());
+        cache.set("plain".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(matches!(cache.rate_limit("plain".to_string(), limit).await, Err(CacheError::WrongType)));
+    }
+
+    #[tokio::test]
+    async fn test_versions_increase_on_every_write() {
+        let cache = create_test_cache(10, 60);
+
+        let v1 = cache.set("key".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let v2 = cache.set("key".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(v2 > v1);
+        assert_eq!(cache.get_versioned("key").await, Some((json!(2).into(), v2)));
+
+        // 删除后重新创建，版本号也不会回退
+        cache.delete("key").await.unwrap();
+        let v3 = cache.set("key".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(v3 > v2);
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_set() {
+        let cache = create_test_cache(10, 60);
+
+        // 版本 0 表示仅当键不存在时创建
+        let v1 = cache.compare_and_set("key".to_string(), json!(1), CacheItemTTL::Permanent, vec![], 0).await.unwrap();
+        let result = cache.compare_and_set("key".to_string(), json!(9), CacheItemTTL::Permanent, vec![], 0).await;
+        assert!(matches!(result, Err(CacheError::VersionMismatch { expected: 0, current }) if current == v1));
+
+        let v2 = cache.compare_and_set("key".to_string(), json!(2), CacheItemTTL::Permanent, vec![], v1).await.unwrap();
+        assert_eq!(cache.get("key").await, Some(json!(2)));
+
+        // 使用过期的版本号写入失败，值保持不变
+        let result = cache.compare_and_set("key".to_string(), json!(3), CacheItemTTL::Permanent, vec![], v1).await;
+        assert!(matches!(result, Err(CacheError::VersionMismatch { current, .. }) if current == v2));
+        assert_eq!(cache.get("key").await, Some(json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_compare_and_set_only_one_wins() {
+        let cache = Arc::new(create_test_cache(10, 60));
+        let v1 = cache.set("counter".to_string(), json!(0), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        let mut handles = Vec::new();
+        for i in 0..8 {
+            let cache = Arc::clone(&cache);
+            handles.push(tokio::spawn(async move {
+                cache.compare_and_set("counter".to_string(), json!(i), CacheItemTTL::Permanent, vec![], v1).await
+            }));
+        }
+        let mut wins = 0;
+        for handle in handles {
+            if handle.await.unwrap().is_ok() {
+                wins += 1;
+            }
+        }
+        assert_eq!(wins, 1);
+    }
+
+    #[tokio::test]
+    async fn test_increment_integers_and_floats() {
+        let cache = create_test_cache(10, 60);
+
+        // 键不存在时以 initial 为初值
+        let (value, _) = cache.increment("n".to_string(), Number::from(5), Number::from(10), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(value, Number::from(15));
+
+        let (value, _) = cache.increment("n".to_string(), Number::from(-20), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(value, Number::from(-5));
+
+        let (value, _) = cache.increment("n".to_string(), Number::from_f64(0.5).unwrap(), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(value.as_f64(), Some(-4.5));
+        assert_eq!(cache.get("n").await, Some(json!(-4.5)));
+    }
+
+    #[tokio::test]
+    async fn test_increment_rejects_non_numeric_and_overflow() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("s".to_string(), json!("abc"), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let result = cache.increment("s".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await;
+        assert!(matches!(result, Err(CacheError::NotNumeric)));
+        assert_eq!(cache.get("s").await, Some(json!("abc")));
+
+        // 超过 i64::MAX 的整数按 u64 精确计算
+        cache.set("max".to_string(), json!(i64::MAX), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let (value, _) = cache.increment("max".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(value, Number::from(i64::MAX as u64 + 1));
+        let (value, _) = cache.increment("max".to_string(), Number::from(-1), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(value, Number::from(i64::MAX));
+
+        cache.set("max".to_string(), json!(u64::MAX), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let result = cache.increment("max".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await;
+        assert!(matches!(result, Err(CacheError::NumericOverflow)));
+
+        cache.set("min".to_string(), json!(i64::MIN), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let result = cache.increment("min".to_string(), Number::from(-1), Number::from(0), CacheItemTTL::Permanent).await;
+        assert!(matches!(result, Err(CacheError::NumericOverflow)));
+    }
+
+    #[tokio::test]
+    async fn test_increment_keeps_existing_ttl() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("n".to_string(), json!(1), CacheItemTTL::Custom(Duration::from_millis(200)), vec![]).await.unwrap();
+        cache.increment("n".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(cache.get("n").await, Some(json!(2)));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(cache.get("n").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_increments_are_atomic() {
+        let cache = Arc::new(create_test_cache(10, 60));
+
+        let mut handles = Vec::new();
+        for _ in 0..50 {
+            let cache = Arc::clone(&cache);
+            handles.push(tokio::spawn(async move {
+                cache.increment("counter".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        assert_eq!(cache.get("counter").await, Some(json!(50)));
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch() {
+        let cache = create_test_cache(10, 60);
+        cache.set("user".to_string(), json!({"name": "a", "age": 1, "tags": ["x"]}), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        let patch = JsonPatch::Merge(json!({"age": 2, "tags": null, "city": "b"}));
+        let (value, _) = cache.patch("user", &patch).await.unwrap();
+        assert_eq!(value, json!({"name": "a", "age": 2, "city": "b"}));
+        assert_eq!(cache.get("user").await, Some(value));
+
+        let result = cache.patch("missing", &patch).await;
+        assert!(matches!(result, Err(CacheError::KeyNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_json_patch_is_all_or_nothing() {
+        let cache = create_test_cache(10, 60);
+        cache.set("doc".to_string(), json!({"a": 1, "b": [1, 2]}), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        let ops: json_patch::Patch = serde_json::from_value(json!([
+            {"op": "replace", "path": "/a", "value": 10},
+            {"op": "add", "path": "/b/-", "value": 3}
+        ])).unwrap();
+        let (value, _) = cache.patch("doc", &JsonPatch::Operations(ops)).await.unwrap();
+        assert_eq!(value, json!({"a": 10, "b": [1, 2, 3]}));
+
+        // test 操作失败时，前面的修改也不会生效
+        let ops: json_patch::Patch = serde_json::from_value(json!([
+            {"op": "replace", "path": "/a", "value": 99},
+            {"op": "test", "path": "/b/0", "value": 42}
+        ])).unwrap();
+        let result = cache.patch("doc", &JsonPatch::Operations(ops)).await;
+        assert!(matches!(result, Err(CacheError::PatchFailed(_))));
+        assert_eq!(cache.get("doc").await, Some(json!({"a": 10, "b": [1, 2, 3]})));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_patches_to_different_fields() {
+        let cache = Arc::new(create_test_cache(10, 60));
+        cache.set("doc".to_string(), json!({}), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        let mut handles = Vec::new();
+        for i in 0..20 {
+            let cache = Arc::clone(&cache);
+            handles.push(tokio::spawn(async move {
+                let patch = JsonPatch::Merge(json!({ format!("f{}", i): i }));
+                cache.patch("doc", &patch).await
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        let doc = cache.get("doc").await.unwrap();
+        assert_eq!(doc.as_object().unwrap().len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_tag() {
+        let cache = create_test_cache(10, 60);
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        cache.set("a".to_string(), json!(1), CacheItemTTL::Permanent, tags(&["user:42", "catalog"])).await.unwrap();
+        cache.set("b".to_string(), json!(2), CacheItemTTL::Permanent, tags(&["user:42"])).await.unwrap();
+        cache.set("c".to_string(), json!(3), CacheItemTTL::Permanent, tags(&["catalog"])).await.unwrap();
+        cache.set("d".to_string(), json!(4), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        assert_eq!(cache.invalidate_tag("user:42").await, 2);
+        assert!(cache.get("a").await.is_none());
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("c").await.is_some());
+        assert!(cache.get("d").await.is_some());
+        assert_eq!(cache.invalidate_tag("user:42").await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_tags_follow_latest_write() {
+        let cache = create_test_cache(10, 60);
+
+        cache.set("k".to_string(), json!(1), CacheItemTTL::Permanent, vec!["t".to_string()]).await.unwrap();
+        // 原子修改保留标签，覆盖写入以新的标签为准
+        cache.increment("k".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await.unwrap();
+        assert_eq!(cache.invalidate_tag("t").await, 1);
+
+        cache.set("k".to_string(), json!(1), CacheItemTTL::Permanent, vec!["t".to_string()]).await.unwrap();
+        cache.set("k".to_string(), json!(2), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert_eq!(cache.invalidate_tag("t").await, 0);
+        assert_eq!(cache.get("k").await, Some(json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_keys_with_prefix_and_cursor() {
+        let cache = create_test_cache(100, 60);
+        for key in ["user:3", "user:1", "order:1", "user:2", "user:4"] {
+            cache.set(key.to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        }
+        cache.set("user:0".to_string(), json!(0), CacheItemTTL::Custom(Duration::from_millis(10)), vec![]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(cache.keys("user:", None, 2), vec!["user:1", "user:2"]);
+        assert_eq!(cache.keys("user:", Some("user:2"), 2), vec!["user:3", "user:4"]);
+        assert!(cache.keys("user:", Some("user:4"), 2).is_empty());
+        assert_eq!(cache.keys("", None, 10).len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_keep_ttl_and_versions() {
+        let cache = create_test_cache(10, 60);
+        cache.set("short".to_string(), json!(1), CacheItemTTL::Custom(Duration::from_secs(5)), vec![]).await.unwrap();
+        cache.set("long".to_string(), json!({"a": 2}), CacheItemTTL::Custom(Duration::from_secs(60)), vec!["t".to_string()]).await.unwrap();
+        cache.set("forever".to_string(), json!(3), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let version = cache.set("idle".to_string(), json!(4), CacheItemTTL::Idle(Duration::from_secs(30)), vec![]).await.unwrap();
+
+        let entries = cache.export();
+        assert_eq!(entries.len(), 4);
+
+        // 模拟快照 10 秒后才恢复：剩余 5 秒的条目已过期
+        let restored = create_test_cache(10, 60);
+        assert_eq!(restored.import(entries, Duration::from_secs(10)).await, 3);
+        assert!(restored.get("short").await.is_none());
+        assert_eq!(restored.get("long").await, Some(json!({"a": 2})));
+        assert_eq!(restored.get("forever").await, Some(json!(3)));
+        assert_eq!(restored.get_versioned("idle").await.unwrap().1, version);
+        assert_eq!(restored.invalidate_tag("t").await, 1);
+
+        // 恢复后的写入版本号大于快照中的版本号
+        let next = restored.set("new".to_string(), json!(5), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert!(next > version);
+    }
+
+    #[tokio::test]
+    async fn test_binary_values() {
+        let cache = create_test_cache(10, 60);
+        let image = CacheValue::Binary {
+            content_type: "image/png".to_string(),
+            data: Bytes::from_static(&[0x89, b'P', b'N', b'G', 0, 0xff]),
+        };
+        let version = cache.set("img".to_string(), image.clone(), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        assert_eq!(cache.get_versioned("img").await, Some((image.clone(), version)));
+        assert_eq!(cache.get("img").await, None);
+
+        let result = cache.increment("img".to_string(), Number::from(1), Number::from(0), CacheItemTTL::Permanent).await;
+        assert!(matches!(result, Err(CacheError::NotNumeric)));
+        let result = cache.patch("img", &JsonPatch::Merge(json!({"a": 1}))).await;
+        assert!(matches!(result, Err(CacheError::PatchFailed(_))));
+
+        // 快照中以 base64 保存，恢复后与原值相同
+        let restored = create_test_cache(10, 60);
+        restored.import(cache.export(), Duration::ZERO).await;
+        assert_eq!(restored.get_versioned("img").await, Some((image, version)));
+    }
+}
@@ -7,7 +7,9 @@ use std::sync::Arc;
 #[derive(Clone)]
 pub struct Cluster {
     ring: HashRing<String>,
-    
+    // 集群中的全部节点，用于需要广播到每个节点的操作
+    nodes: Vec<String>,
+
     pub my_addr: String,
 }
 
@@ -23,21 +25,59 @@ impl Cluster {
 
         Self {
             ring,
+            nodes: settings.cluster_nodes.clone(),
             my_addr: settings.my_connectable_addr.clone(),
         }
     }
 
+    /// 键的所有者由它的哈希槽决定，哈希标签相同的键总在同一个节点上
     pub fn get_node_for_key(&self, key: &str) -> String {
         self.ring
-            .node(&String::from(key))
+            .node(&String::from(hash_slot(key)))
             .unwrap()
             .to_string()
             .clone()
     }
 
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
     #[allow(dead_code)]
     pub fn is_key_local(&self, key: &str) -> bool {
         let target_addr = self.get_node_for_key(key);
         target_addr == self.my_addr
     }
 }
+
+/// 键的哈希槽：键中第一对 `{...}` 之间的内容（哈希标签），
+/// 没有标签或标签为空时为整个键，与 Redis Cluster 的规则相同。
+/// 例如 `{user42}:profile` 与 `{user42}:session` 属于同一个槽。
+pub fn hash_slot(key: &str) -> &str {
+    if let Some(open) = key.find('{')
+        && let Some(len) = key[open + 1..].find('}')
+        && len > 0
+    {
+        return &key[open + 1..open + 1 + len];
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_slot() {
+        assert_eq!(hash_slot("{user42}:profile"), "user42");
+        assert_eq!(hash_slot("session:{user42}"), "user42");
+        // 只看第一对花括号
+        assert_eq!(hash_slot("{a}{b}"), "a");
+        assert_eq!(hash_slot("x{a}y{b}"), "a");
+        // 没有标签或标签为空时使用整个键
+        assert_eq!(hash_slot("plain"), "plain");
+        assert_eq!(hash_slot("{}:profile"), "{}:profile");
+        assert_eq!(hash_slot("{user42"), "{user42");
+        assert_eq!(hash_slot("}{user42}"), "user42");
+    }
+}
@@ -1,14 +1,146 @@
 // src/config.rs
 
 use config::{Config, ConfigError, Environment};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use log::info;
 
+/// 缓存写满时的驱逐策略
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionPolicy {
+    // 驱逐最久未被访问的条目
+    Lru,
+    // moka 默认的 TinyLFU：按访问频率决定是否接纳新条目
+    #[serde(alias = "lfu")]
+    TinyLfu,
+    // 按写入顺序驱逐，读取不影响顺序
+    Fifo,
+    // 不驱逐，写满后拒绝新键
+    None,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct CacheSettings {
     pub capacity: u64,
     pub default_ttl_seconds: u64,
+    // 后台清理过期条目的间隔（毫秒）
+    pub cleanup_interval_ms: u64,
+    // 内存预算（字节），设置后按条目大小驱逐，取代 capacity
+    pub max_memory_bytes: Option<u64>,
+    pub eviction_policy: EvictionPolicy,
+    // TTL 抖动：写入时把默认 TTL 和指定 TTL 随机延长至多该百分比，
+    // 避免同一批写入的键同时过期
+    pub ttl_jitter_percent: Option<u32>,
+    // TTL 抖动的绝对范围（毫秒），与百分比同时设置时取较大者
+    pub ttl_jitter_ms: Option<u64>,
+}
+
+/// 单个命名空间的配置，未设置的项沿用 `cache` 中的值
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct NamespaceSettings {
+    pub capacity: Option<u64>,
+    pub default_ttl_seconds: Option<u64>,
+    pub max_memory_bytes: Option<u64>,
+    pub eviction_policy: Option<EvictionPolicy>,
+    pub ttl_jitter_percent: Option<u32>,
+    pub ttl_jitter_ms: Option<u64>,
+}
+
+impl NamespaceSettings {
+    pub fn resolve(&self, base: &CacheSettings) -> CacheSettings {
+        CacheSettings {
+            capacity: self.capacity.unwrap_or(base.capacity),
+            default_ttl_seconds: self.default_ttl_seconds.unwrap_or(base.default_ttl_seconds),
+            cleanup_interval_ms: base.cleanup_interval_ms,
+            max_memory_bytes: self.max_memory_bytes.or(base.max_memory_bytes),
+            eviction_policy: self.eviction_policy.unwrap_or(base.eviction_policy),
+            ttl_jitter_percent: self.ttl_jitter_percent.or(base.ttl_jitter_percent),
+            ttl_jitter_ms: self.ttl_jitter_ms.or(base.ttl_jitter_ms),
+        }
+    }
+}
+
+fn default_origin_timeout_ms() -> u64 {
+    5000
+}
+
+/// 回源配置：未命中时由键的所有者节点从源地址加载
+#[derive(Debug, Deserialize, Clone)]
+pub struct OriginSettings {
+    // 适用的命名空间，未设置时为默认命名空间
+    pub namespace: Option<String>,
+    // 键前缀，为空时匹配命名空间中的所有键
+    #[serde(default)]
+    pub prefix: String,
+    // 源地址，其中的 `{key}` 替换为键；没有占位符时键作为最后一段路径追加
+    pub url: String,
+    // 加载的值的 TTL（秒），未设置时使用命名空间的默认 TTL
+    pub ttl_seconds: Option<u64>,
+    #[serde(default = "default_origin_timeout_ms")]
+    pub timeout_ms: u64,
+    // 过期后仍可返回旧值并在后台刷新的时长（秒），同 HTTP 的 stale-while-revalidate
+    #[serde(default)]
+    pub stale_while_revalidate_seconds: u64,
+    // 过期后源不可用时仍可返回旧值的最长时长（秒），同 HTTP 的 stale-if-error
+    #[serde(default)]
+    pub stale_if_error_seconds: u64,
+}
+
+/// 追加日志的 fsync 策略
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    // 每次写入都等待落盘后才返回
+    Always,
+    // 每秒 fsync 一次，崩溃时最多丢失约一秒的写入
+    #[serde(alias = "everysec")]
+    EverySecond,
+    // 交给操作系统决定何时落盘
+    #[serde(alias = "no")]
+    Never,
+}
+
+/// 追加日志配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct AofSettings {
+    pub enabled: bool,
+    pub path: String,
+    pub fsync: FsyncPolicy,
+    // 日志超过该大小且比上次重写后增长一倍时，在后台重写
+    pub compact_min_bytes: u64,
+}
+
+/// 快照配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct SnapshotSettings {
+    // 快照文件所在目录
+    pub dir: String,
+    // 定期快照的间隔（秒），0 表示只在手动触发时快照
+    pub interval_seconds: u64,
+    // 启动时是否加载最新的快照
+    pub restore_on_boot: bool,
+    // 保留的快照文件数，更早的会被删除
+    pub retain: usize,
+}
+
+/// 写回后端存储的配置
+#[derive(Debug, Deserialize, Clone)]
+pub struct WriteBehindSettings {
+    pub enabled: bool,
+    // redb 数据库文件
+    pub path: String,
+    // 每批最多写入的变更数
+    pub batch_size: usize,
+    // 收到变更后最多等待多久写入（毫秒）
+    pub flush_interval_ms: u64,
+    // 一批写入失败后的重试次数，耗尽后写入死信文件
+    pub max_retries: u32,
+    // 首次重试前的等待（毫秒），之后每次加倍
+    pub retry_backoff_ms: u64,
+    // 死信文件，每行一条未能写入的变更
+    pub dead_letter_path: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -18,6 +150,15 @@ pub struct Settings {
     pub my_connectable_addr: String,
     pub cluster_nodes: Vec<String>,
     pub cache: CacheSettings,
+    // 命名空间名 -> 配置，例如 MY_CACHE_NAMESPACES__ORDERS__CAPACITY=1000
+    #[serde(default)]
+    pub namespaces: HashMap<String, NamespaceSettings>,
+    // 回源名 -> 配置，例如 MY_CACHE_ORIGINS__USERS__URL=http://db-api/users/{key}
+    #[serde(default)]
+    pub origins: HashMap<String, OriginSettings>,
+    pub snapshot: SnapshotSettings,
+    pub aof: AofSettings,
+    pub write_behind: WriteBehindSettings,
     pub log_level: String,
 }
 
@@ -39,6 +180,23 @@ impl Settings {
             .set_default("rpc_addr", "0.0.0.0:50051")?
             .set_default("cache.capacity", 10000)?
             .set_default("cache.default_ttl_seconds", 3600)? 
+            .set_default("cache.cleanup_interval_ms", 1000)?
+            .set_default("cache.eviction_policy", "lru")?
+            .set_default("snapshot.dir", "snapshots")?
+            .set_default("snapshot.interval_seconds", 0)?
+            .set_default("snapshot.restore_on_boot", false)?
+            .set_default("snapshot.retain", 3)?
+            .set_default("aof.enabled", false)?
+            .set_default("aof.path", "appendonly.aof")?
+            .set_default("aof.fsync", "every_second")?
+            .set_default("aof.compact_min_bytes", 64 * 1024 * 1024)?
+            .set_default("write_behind.enabled", false)?
+            .set_default("write_behind.path", "backing.redb")?
+            .set_default("write_behind.batch_size", 500)?
+            .set_default("write_behind.flush_interval_ms", 1000)?
+            .set_default("write_behind.max_retries", 5)?
+            .set_default("write_behind.retry_backoff_ms", 200)?
+            .set_default("write_behind.dead_letter_path", "write-behind.dead.jsonl")?
             .set_default("log_level", "info")?
 
             .add_source(
@@ -54,8 +212,121 @@ impl Settings {
                     .list_separator(",")
             )
             .build()?;
-        s.try_deserialize()
+        let settings: Settings = s.try_deserialize()?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// 检查反序列化无法表达的约束，启动时尽早报错
+    fn validate(&self) -> Result<(), ConfigError> {
+        // tokio::time::interval 不接受零间隔
+        if self.cache.cleanup_interval_ms == 0 {
+            return Err(ConfigError::Message(
+                "cache.cleanup_interval_ms must be greater than 0".to_string(),
+            ));
+        }
+        let jitter_percents = std::iter::once(("cache", self.cache.ttl_jitter_percent)).chain(
+            self.namespaces
+                .iter()
+                .map(|(name, ns)| (name.as_str(), ns.ttl_jitter_percent)),
+        );
+        for (scope, percent) in jitter_percents {
+            if percent.is_some_and(|percent| percent > 100) {
+                return Err(ConfigError::Message(format!(
+                    "ttl_jitter_percent of '{}' must be at most 100",
+                    scope
+                )));
+            }
+        }
+        Ok(())
     }
 }
 
-pub type SharedSettings = Arc<Settings>;
\ No newline at end of file
+pub type SharedSettings = Arc<Settings>;
+
+#[cfg(test)]
+impl CacheSettings {
+    /// 单元测试使用的缓存配置，测试只覆盖自己关心的字段
+    pub fn for_tests() -> Self {
+        Self {
+            capacity: 100,
+            default_ttl_seconds: 60,
+            cleanup_interval_ms: 1000,
+            max_memory_bytes: None,
+            eviction_policy: EvictionPolicy::Lru,
+            ttl_jitter_percent: None,
+            ttl_jitter_ms: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Settings {
+    /// 单元测试使用的配置：单节点，不持久化，不回源
+    pub fn for_tests(cache: CacheSettings) -> Self {
+        Self {
+            http_addr: "127.0.0.1:0".to_string(),
+            rpc_addr: "127.0.0.1:0".to_string(),
+            my_connectable_addr: "127.0.0.1:0".to_string(),
+            cluster_nodes: vec![],
+            cache,
+            namespaces: HashMap::new(),
+            origins: HashMap::new(),
+            snapshot: SnapshotSettings {
+                dir: "snapshots".to_string(),
+                interval_seconds: 0,
+                restore_on_boot: false,
+                retain: 3,
+            },
+            aof: AofSettings {
+                enabled: false,
+                path: "appendonly.aof".to_string(),
+                fsync: FsyncPolicy::EverySecond,
+                compact_min_bytes: 64 * 1024 * 1024,
+            },
+            write_behind: WriteBehindSettings {
+                enabled: false,
+                path: "backing.redb".to_string(),
+                batch_size: 500,
+                flush_interval_ms: 1000,
+                max_retries: 5,
+                retry_backoff_ms: 200,
+                dead_letter_path: "write-behind.dead.jsonl".to_string(),
+            },
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_zero_cleanup_interval() {
+        let mut settings = Settings::for_tests(CacheSettings {
+            capacity: 10,
+            ..CacheSettings::for_tests()
+        });
+        assert!(settings.validate().is_ok());
+
+        settings.cache.cleanup_interval_ms = 0;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_jitter_percent_over_100() {
+        let mut settings = Settings::for_tests(CacheSettings {
+            capacity: 10,
+            ttl_jitter_percent: Some(100),
+            ..CacheSettings::for_tests()
+        });
+        assert!(settings.validate().is_ok());
+
+        settings.namespaces.insert(
+            "orders".to_string(),
+            NamespaceSettings { ttl_jitter_percent: Some(150), ..Default::default() },
+        );
+        assert!(settings.validate().is_err());
+    }
+}
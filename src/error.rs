@@ -7,6 +7,7 @@ use axum::{
 };
 use serde_json::json;
 use thiserror::Error;
+use tonic::metadata::MetadataValue;
 use log::error;
 
 #[derive(Error, Debug)]
@@ -18,8 +19,11 @@ pub enum AppError {
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+
     #[error("Internal cluster RPC error: {0}")]
-    RpcError(#[from] RpcClientError),
+    RpcError(RpcClientError),
 
     #[error("Internal server error: {0}")]
     InternalError(String),
@@ -31,6 +35,18 @@ impl From<serde_json::Error> for AppError {
     }
 }
 
+impl From<RpcClientError> for AppError {
+    fn from(err: RpcClientError) -> Self {
+        // 目标节点上的缓存错误原样还给客户端，而不是笼统的 500
+        if let RpcClientError::Status(status) = &err
+            && let Some(cache_err) = CacheError::from_status(status)
+        {
+            return AppError::Cache(cache_err);
+        }
+        AppError::RpcError(err)
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         match self {
@@ -41,6 +57,40 @@ impl IntoResponse for AppError {
                 let body = Json(json!({ "error": msg }));
                 (StatusCode::BAD_REQUEST, body).into_response()
             }
+            AppError::Cache(cache_err) => {
+                let msg = cache_err.to_string();
+                let (status, body) = match cache_err {
+                    CacheError::CacheFull => {
+                        (StatusCode::INSUFFICIENT_STORAGE, json!({ "error": msg }))
+                    }
+                    CacheError::VersionMismatch { current, .. } => (
+                        StatusCode::PRECONDITION_FAILED,
+                        json!({ "error": msg, "current_version": current }),
+                    ),
+                    CacheError::NotNumeric
+                    | CacheError::NumericOverflow
+                    | CacheError::PatchFailed(_)
+                    | CacheError::WrongType => {
+                        (StatusCode::CONFLICT, json!({ "error": msg }))
+                    }
+                    CacheError::KeyNotFound | CacheError::UnknownNamespace(_) => {
+                        (StatusCode::NOT_FOUND, json!({ "error": msg }))
+                    }
+                    CacheError::OriginFailed(_) => {
+                        (StatusCode::BAD_GATEWAY, json!({ "error": msg }))
+                    }
+                    CacheError::CrossSlot | CacheError::ZeroLease => {
+                        (StatusCode::BAD_REQUEST, json!({ "error": msg }))
+                    }
+                    CacheError::LockHeld(holder) => {
+                        (StatusCode::CONFLICT, json!({ "error": msg, "holder": holder }))
+                    }
+                    CacheError::NotLockHolder | CacheError::CollectionTooLarge => {
+                        (StatusCode::CONFLICT, json!({ "error": msg }))
+                    }
+                };
+                (status, Json(body)).into_response()
+            }
             AppError::RpcError(rpc_err) => {
                 error!("Internal RPC error: {:?}", rpc_err);
                 let body = Json(json!({ "error": "Internal cluster communication failed" }));
@@ -67,9 +117,162 @@ pub enum RpcClientError {
     #[error("gRPC transport error: {0}")]
     Transport(#[from] tonic::transport::Error),
 
+    // tonic::Status 体积较大，装箱后避免所有 Result 跟着变大
     #[error("gRPC call failed: {0}")]
-    Status(#[from] tonic::Status),
+    Status(Box<tonic::Status>),
 
     #[error("JSON serialization/deserialization error: {0}")]
     Json(#[from] serde_json::Error),
-}
\ No newline at end of file
+}
+
+impl From<tonic::Status> for RpcClientError {
+    fn from(status: tonic::Status) -> Self {
+        RpcClientError::Status(Box::new(status))
+    }
+}
+
+
+// --- Cache Error ---
+// -------------------
+// `cache.rs` 专用的错误类型。
+// 节点间转发时通过 gRPC 状态及其元数据传递，
+// 由 `to_status` / `from_status` 互相转换。
+
+#[derive(Error, Debug, Clone)]
+pub enum CacheError {
+    #[error("Cache is full")]
+    CacheFull,
+
+    #[error("Version mismatch: expected {expected}, current {current}")]
+    VersionMismatch { expected: u64, current: u64 },
+
+    #[error("Value is not a number")]
+    NotNumeric,
+
+    #[error("Numeric overflow")]
+    NumericOverflow,
+
+    #[error("Key not found")]
+    KeyNotFound,
+
+    #[error("Patch failed: {0}")]
+    PatchFailed(String),
+
+    #[error("Unknown namespace: {0}")]
+    UnknownNamespace(String),
+
+    #[error("Origin request failed: {0}")]
+    OriginFailed(String),
+
+    #[error("Operation against a key holding the wrong kind of value")]
+    WrongType,
+
+    #[error("Keys in a transaction must share the same hash slot")]
+    CrossSlot,
+
+    #[error("Lock is held by '{0}'")]
+    LockHeld(String),
+
+    #[error("Lock is not held with the given token")]
+    NotLockHolder,
+
+    #[error("Lock lease must be positive")]
+    ZeroLease,
+
+    #[error("Collection would exceed {} elements", crate::cache::MAX_COLLECTION_LEN)]
+    CollectionTooLarge,
+}
+
+// 随 gRPC 状态一起传递的元数据
+const ERROR_KIND_KEY: &str = "x-cache-error";
+const EXPECTED_VERSION_KEY: &str = "x-expected-version";
+const CURRENT_VERSION_KEY: &str = "x-current-version";
+const ERROR_DETAIL_KEY: &str = "x-error-detail";
+
+impl CacheError {
+    fn kind(&self) -> &'static str {
+        match self {
+            CacheError::CacheFull => "cache_full",
+            CacheError::VersionMismatch { .. } => "version_mismatch",
+            CacheError::NotNumeric => "not_numeric",
+            CacheError::NumericOverflow => "numeric_overflow",
+            CacheError::KeyNotFound => "key_not_found",
+            CacheError::PatchFailed(_) => "patch_failed",
+            CacheError::UnknownNamespace(_) => "unknown_namespace",
+            CacheError::OriginFailed(_) => "origin_failed",
+            CacheError::WrongType => "wrong_type",
+            CacheError::CrossSlot => "cross_slot",
+            CacheError::LockHeld(_) => "lock_held",
+            CacheError::NotLockHolder => "not_lock_holder",
+            CacheError::ZeroLease => "zero_lease",
+            CacheError::CollectionTooLarge => "collection_too_large",
+        }
+    }
+
+    pub fn to_status(&self) -> tonic::Status {
+        let code = match self {
+            CacheError::CacheFull => tonic::Code::ResourceExhausted,
+            CacheError::VersionMismatch { .. }
+            | CacheError::NotNumeric
+            | CacheError::NumericOverflow
+            | CacheError::PatchFailed(_)
+            | CacheError::WrongType
+            | CacheError::LockHeld(_)
+            | CacheError::NotLockHolder
+            | CacheError::CollectionTooLarge => tonic::Code::FailedPrecondition,
+            CacheError::KeyNotFound | CacheError::UnknownNamespace(_) => tonic::Code::NotFound,
+            CacheError::OriginFailed(_) => tonic::Code::Internal,
+            CacheError::CrossSlot | CacheError::ZeroLease => tonic::Code::InvalidArgument,
+        };
+
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        metadata.insert(ERROR_KIND_KEY, MetadataValue::from_static(self.kind()));
+        if let CacheError::VersionMismatch { expected, current } = self {
+            metadata.insert(EXPECTED_VERSION_KEY, MetadataValue::from(*expected));
+            metadata.insert(CURRENT_VERSION_KEY, MetadataValue::from(*current));
+        }
+        if let CacheError::PatchFailed(detail)
+        | CacheError::UnknownNamespace(detail)
+        | CacheError::OriginFailed(detail)
+        | CacheError::LockHeld(detail) = self
+            && let Ok(value) = detail.parse()
+        {
+            metadata.insert(ERROR_DETAIL_KEY, value);
+        }
+        tonic::Status::with_metadata(code, self.to_string(), metadata)
+    }
+
+    pub fn from_status(status: &tonic::Status) -> Option<Self> {
+        let metadata_str = |key: &str| status.metadata().get(key).and_then(|v| v.to_str().ok());
+        let metadata_u64 = |key: &str| metadata_str(key).and_then(|v| v.parse::<u64>().ok());
+
+        match metadata_str(ERROR_KIND_KEY)? {
+            "cache_full" => Some(CacheError::CacheFull),
+            "version_mismatch" => Some(CacheError::VersionMismatch {
+                expected: metadata_u64(EXPECTED_VERSION_KEY)?,
+                current: metadata_u64(CURRENT_VERSION_KEY)?,
+            }),
+            "not_numeric" => Some(CacheError::NotNumeric),
+            "numeric_overflow" => Some(CacheError::NumericOverflow),
+            "key_not_found" => Some(CacheError::KeyNotFound),
+            "patch_failed" => Some(CacheError::PatchFailed(
+                metadata_str(ERROR_DETAIL_KEY).unwrap_or_default().to_string(),
+            )),
+            "unknown_namespace" => Some(CacheError::UnknownNamespace(
+                metadata_str(ERROR_DETAIL_KEY).unwrap_or_default().to_string(),
+            )),
+            "origin_failed" => Some(CacheError::OriginFailed(
+                metadata_str(ERROR_DETAIL_KEY).unwrap_or_default().to_string(),
+            )),
+            "wrong_type" => Some(CacheError::WrongType),
+            "cross_slot" => Some(CacheError::CrossSlot),
+            "lock_held" => Some(CacheError::LockHeld(
+                metadata_str(ERROR_DETAIL_KEY).unwrap_or_default().to_string(),
+            )),
+            "not_lock_holder" => Some(CacheError::NotLockHolder),
+            "zero_lease" => Some(CacheError::ZeroLease),
+            "collection_too_large" => Some(CacheError::CollectionTooLarge),
+            _ => None,
+        }
+    }
+}
@@ -1,46 +1,65 @@
 // src/http_server.rs
 use crate::{
-    cache::{CacheItemTTL, SharedCache},
-    cluster::SharedCluster,
+    aof::Aof,
+    cache::{CacheItemTTL, CacheValue, EntryMeta, EntryTtl, Freshness, JsonPatch, ListEnd, RateLimit, TxnOp, WatchItem, ZsetRange, forward_events, integer, number_from_i128},
+    cluster::{SharedCluster, hash_slot},
     config::SharedSettings,
-    error::AppError,
+    error::{AppError, CacheError},
+    namespace::SharedNamespaces,
     rpc_client::RpcClient,
+    snapshot,
+    write_behind::WriteBehind,
 };
 #[allow(unused_imports)]
 use axum::{
     Json, Router,
+    body::Bytes,
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::{delete, get, post},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}},
+    routing::{delete, get, patch, post, put},
 };
-use log::info;
-use serde::Deserialize;
-use serde_json::{Map, Value, json};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_stream::wrappers::ReceiverStream;
 use tower_http::cors::{Any, CorsLayer};
 
 #[derive(Clone)]
 struct AppState {
-    cache: SharedCache,
+    settings: SharedSettings,
+    namespaces: SharedNamespaces,
     cluster: SharedCluster,
     rpc_client: RpcClient,
+    aof: Option<Aof>,
+    write_behind: Option<WriteBehind>,
 }
 
 pub async fn run_http_server(
     settings: SharedSettings,
-    cache: SharedCache,
+    namespaces: SharedNamespaces,
     cluster: SharedCluster,
+    aof: Option<Aof>,
+    write_behind: Option<WriteBehind>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let addr: SocketAddr = settings.http_addr.parse()?;
 
     let rpc_client = RpcClient::new(3000); // 3 秒连接超时
 
     let app_state = AppState {
-        cache,
+        settings: Arc::clone(&settings),
+        namespaces,
         cluster,
         rpc_client,
+        aof,
+        write_behind,
     };
 
     let cors = CorsLayer::new()
@@ -48,10 +67,87 @@ pub async fn run_http_server(
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // 静态路由优先于 `/:key`，与管理接口同名的键（见 `RESERVED_KEYS`）无法通过 HTTP 访问，
+    // 因此写入时直接拒绝
     let app = Router::new()
         .route("/", post(handler_post_set)) // [cite: 13]
         .route("/:key", get(handler_get)) // [cite: 15]
+        .route("/:key", put(handler_put))
         .route("/:key", delete(handler_delete)) // [cite: 19]
+        .route("/:key", patch(handler_patch))
+        .route("/:key/incr", post(handler_incr))
+        .route("/:key/decr", post(handler_decr))
+        .route("/:key/meta", get(handler_get_meta))
+        .route("/:key/expire", post(handler_expire))
+        .route("/:key/persist", post(handler_persist))
+        .route("/:key/ttl", get(handler_get_ttl))
+        .route("/:key/len", get(handler_len))
+        .route("/:key/fields", get(handler_hash_get_all))
+        .route("/:key/fields/:field", get(handler_hash_get))
+        .route("/:key/fields/:field", put(handler_hash_set))
+        .route("/:key/fields/:field", delete(handler_hash_delete))
+        .route("/:key/items", get(handler_list_range))
+        .route("/:key/items", post(handler_list_push))
+        .route("/:key/items/pop", post(handler_list_pop))
+        .route("/:key/items/trim", post(handler_list_trim))
+        .route("/:key/members", get(handler_set_members))
+        .route("/:key/members", post(handler_set_add))
+        .route("/:key/members/:member", get(handler_set_contains))
+        .route("/:key/members/:member", delete(handler_set_remove))
+        .route("/:key/scores", get(handler_zset_range))
+        .route("/:key/scores", post(handler_zset_add))
+        .route("/:key/scores/:member", get(handler_zset_rank))
+        .route("/:key/scores/:member", delete(handler_zset_remove))
+        .route("/:key/lock", get(handler_lock_info))
+        .route("/:key/lock", post(handler_lock_acquire))
+        .route("/:key/lock", delete(handler_lock_release))
+        .route("/:key/lock/renew", post(handler_lock_renew))
+        .route("/:key/ratelimit", post(handler_rate_limit))
+        .route("/keys", get(handler_list_keys))
+        .route("/watch", get(handler_watch))
+        .route("/txn", post(handler_transaction))
+        .route("/tags/:tag", delete(handler_invalidate_tag))
+        .route("/ns/:namespace", post(handler_post_set))
+        .route("/ns/:namespace/:key", get(handler_get))
+        .route("/ns/:namespace/:key", put(handler_put))
+        .route("/ns/:namespace/:key", delete(handler_delete))
+        .route("/ns/:namespace/:key", patch(handler_patch))
+        .route("/ns/:namespace/:key/incr", post(handler_incr))
+        .route("/ns/:namespace/:key/decr", post(handler_decr))
+        .route("/ns/:namespace/:key/meta", get(handler_get_meta))
+        .route("/ns/:namespace/:key/expire", post(handler_expire))
+        .route("/ns/:namespace/:key/persist", post(handler_persist))
+        .route("/ns/:namespace/:key/ttl", get(handler_get_ttl))
+        .route("/ns/:namespace/:key/len", get(handler_len))
+        .route("/ns/:namespace/:key/fields", get(handler_hash_get_all))
+        .route("/ns/:namespace/:key/fields/:field", get(handler_hash_get))
+        .route("/ns/:namespace/:key/fields/:field", put(handler_hash_set))
+        .route("/ns/:namespace/:key/fields/:field", delete(handler_hash_delete))
+        .route("/ns/:namespace/:key/items", get(handler_list_range))
+        .route("/ns/:namespace/:key/items", post(handler_list_push))
+        .route("/ns/:namespace/:key/items/pop", post(handler_list_pop))
+        .route("/ns/:namespace/:key/items/trim", post(handler_list_trim))
+        .route("/ns/:namespace/:key/members", get(handler_set_members))
+        .route("/ns/:namespace/:key/members", post(handler_set_add))
+        .route("/ns/:namespace/:key/members/:member", get(handler_set_contains))
+        .route("/ns/:namespace/:key/members/:member", delete(handler_set_remove))
+        .route("/ns/:namespace/:key/scores", get(handler_zset_range))
+        .route("/ns/:namespace/:key/scores", post(handler_zset_add))
+        .route("/ns/:namespace/:key/scores/:member", get(handler_zset_rank))
+        .route("/ns/:namespace/:key/scores/:member", delete(handler_zset_remove))
+        .route("/ns/:namespace/:key/lock", get(handler_lock_info))
+        .route("/ns/:namespace/:key/lock", post(handler_lock_acquire))
+        .route("/ns/:namespace/:key/lock", delete(handler_lock_release))
+        .route("/ns/:namespace/:key/lock/renew", post(handler_lock_renew))
+        .route("/ns/:namespace/:key/ratelimit", post(handler_rate_limit))
+        .route("/ns/:namespace/keys", get(handler_list_keys))
+        .route("/ns/:namespace/watch", get(handler_watch))
+        .route("/ns/:namespace/txn", post(handler_transaction))
+        .route("/ns/:namespace/tags/:tag", delete(handler_invalidate_tag))
+        .route("/stats", get(handler_stats))
+        .route("/stats/write-behind", get(handler_write_behind_stats))
+        .route("/snapshot", post(handler_snapshot))
+        .route("/aof/compact", post(handler_compact_aof))
         .with_state(app_state) // 注入共享状态
         .layer(cors);
 
@@ -63,14 +159,53 @@ pub async fn run_http_server(
     Ok(())
 }
 
+/// `/ns/:namespace/...` 路由带命名空间，原有路由不带，即使用默认命名空间
+#[derive(Debug, Deserialize)]
+struct NamespacePath {
+    namespace: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyPath {
+    namespace: Option<String>,
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FieldPath {
+    namespace: Option<String>,
+    key: String,
+    field: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemberPath {
+    namespace: Option<String>,
+    key: String,
+    member: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagPath {
+    namespace: Option<String>,
+    tag: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct PostQuery {
     ttl: Option<String>,
+    tti: Option<String>,
+    // 逗号分隔的标签列表，例如 `?tags=user:42,catalog`
+    tags: Option<String>,
+    // `?jitter=false` 时不加 TTL 抖动
+    jitter: Option<bool>,
 }
 
 async fn handler_post_set(
     State(state): State<AppState>,
+    ns_path: Option<Path<NamespacePath>>,
     Query(query): Query<PostQuery>,
+    headers: HeaderMap,
     Json(payload): Json<Value>,
 ) -> Result<impl IntoResponse, AppError> {
     let mut map = payload
@@ -87,88 +222,1638 @@ async fn handler_post_set(
     let (key, value) = map
         .remove_entry(map.clone().keys().next().unwrap())
         .unwrap();
+    let namespace = ns_path.and_then(|Path(p)| p.namespace).unwrap_or_default();
     info!("Received SET for '{}' - '{}'", key, value);
 
-    let ttl = parse_ttl_query(query.ttl);
+    let version = write_value(&state, namespace, key, CacheValue::Json(value), query, &headers).await?;
+    Ok((StatusCode::OK, [(header::ETAG, format_etag(version))]))
+}
 
-    let target_addr = state.cluster.get_node_for_key(&key);
+/// 以请求体原样作为值写入，并记录其 Content-Type。
+/// `application/json` 的请求体按 JSON 保存，与 `POST /` 写入的值相同。
+async fn handler_put(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<PostQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_BINARY_CONTENT_TYPE.to_string());
 
-    if target_addr == state.cluster.my_addr {
-        info!("Handling SET locally : {} - {}", key, value);
-        state.cache.set(key, value, ttl).await;
+    let value = if is_json_content_type(&content_type) {
+        CacheValue::Json(serde_json::from_slice(&body)?)
     } else {
-        info!(
-            "Forwarding SET for key-value: '{}' - '{}' to {}",
-            key, value, target_addr
-        );
-        state
-            .rpc_client
-            .forward_set(key, value, ttl, &target_addr)
-            .await?;
+        CacheValue::Binary { content_type, data: body }
+    };
+    info!("Received PUT for '{}' - {}", key, value);
+
+    let version = write_value(&state, namespace.unwrap_or_default(), key, value, query, &headers).await?;
+    Ok((StatusCode::OK, [(header::ETAG, format_etag(version))]))
+}
+
+/// 与管理接口路径同名、被静态路由遮住的键名
+const RESERVED_KEYS: &[&str] = &["keys", "stats", "snapshot", "aof", "watch", "txn", "tags", "ns"];
+
+fn check_reserved_key(key: &str) -> Result<(), AppError> {
+    if RESERVED_KEYS.contains(&key) {
+        return Err(AppError::InvalidInput(format!(
+            "Key '{}' is reserved for an admin route and cannot be read back over HTTP",
+            key
+        )));
     }
+    Ok(())
+}
+
+/// 写入一个值：带 If-Match / If-None-Match 时为 CAS，键不属于本节点时转发给所有者
+async fn write_value(
+    state: &AppState,
+    namespace: String,
+    key: String,
+    value: CacheValue,
+    query: PostQuery,
+    headers: &HeaderMap,
+) -> Result<u64, AppError> {
+    check_reserved_key(&key)?;
+    let cache = state.namespaces.get(&namespace)?;
 
-    Ok(StatusCode::OK)
+    let ttl = EntryTtl {
+        ttl: parse_ttl_query(query.ttl, query.tti).map_err(AppError::InvalidInput)?,
+        jitter: query.jitter != Some(false),
+    };
+    let expected_version = parse_precondition(headers).map_err(AppError::InvalidInput)?;
+    let tags = parse_tags(query.tags.as_deref());
+
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let version = match expected_version {
+        None if target_addr == state.cluster.my_addr => {
+            info!("Handling SET locally : {} - {}", key, value);
+            cache.set(key, value, ttl, tags).await?
+        }
+        None => {
+            info!(
+                "Forwarding SET for key-value: '{}' - '{}' to {}",
+                key, value, target_addr
+            );
+            state
+                .rpc_client
+                .forward_set(&namespace, key, value, ttl, tags, &target_addr)
+                .await?
+        }
+        Some(expected) if target_addr == state.cluster.my_addr => {
+            info!("Handling CAS locally : {} - {} (expected version {})", key, value, expected);
+            cache.compare_and_set(key, value, ttl, tags, expected).await?
+        }
+        Some(expected) => {
+            info!(
+                "Forwarding CAS for key-value: '{}' - '{}' (expected version {}) to {}",
+                key, value, expected, target_addr
+            );
+            state
+                .rpc_client
+                .forward_compare_and_set(&namespace, key, value, ttl, tags, expected, &target_addr)
+                .await?
+        }
+    };
+
+    Ok(version)
 }
 
 async fn handler_get(
     State(state): State<AppState>,
-    Path(key): Path<String>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
 ) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
     let value = {
         let target_addr = state.cluster.get_node_for_key(&key);
 
         if target_addr == state.cluster.my_addr {
             info!("Handling GET for key '{}' locally", key);
-            state.cache.get(&key).await
+            cache.get_or_load(&key).await?
         } else {
             info!("Forwarding GET for key '{}' to {}", key, target_addr);
-            state.rpc_client.forward_get(&key, &target_addr).await.ok()
+            match state.rpc_client.forward_get(&namespace, &key, &target_addr).await {
+                Ok(value) => Some(value),
+                // 所有者回源失败等缓存错误原样返回，其余按未命中处理
+                Err(e) => match AppError::from(e) {
+                    AppError::Cache(CacheError::KeyNotFound) => None,
+                    err @ AppError::Cache(_) => return Err(err),
+                    _ => None,
+                },
+            }
         }
     };
 
-    match value {
-        Some(val) => {
+    let Some((value, version, freshness)) = value else {
+        return Err(AppError::KeyNotFound);
+    };
+
+    // JSON 值和数据结构以 `{key: value}` 返回，二进制值按写入时的 Content-Type 原样返回
+    let mut response = match value {
+        CacheValue::Binary { content_type, data } => (
+            StatusCode::OK,
+            [
+                (header::ETAG, format_etag(version)),
+                (header::CONTENT_TYPE, content_type),
+            ],
+            data,
+        )
+            .into_response(),
+        value => {
             let mut result_map = Map::new();
-            result_map.insert(key, val);
-            Ok((StatusCode::OK, Json(json!(result_map))))
+            result_map.insert(key, value.to_json().unwrap_or_default());
+            (
+                StatusCode::OK,
+                [(header::ETAG, format_etag(version))],
+                Json(json!(result_map)),
+            )
+                .into_response()
         }
-        None => Err(AppError::KeyNotFound),
+    };
+    // 旧值按 HTTP 缓存的约定用 Warning 头标记
+    let warning = match freshness {
+        Freshness::Fresh => None,
+        Freshness::Stale => Some(r#"110 - "Response is Stale""#),
+        Freshness::StaleIfError => Some(r#"111 - "Revalidation Failed""#),
+    };
+    if let Some(warning) = warning {
+        response
+            .headers_mut()
+            .insert(header::WARNING, HeaderValue::from_static(warning));
     }
+    Ok(response)
 }
 
-async fn handler_delete(
+/// 键的元数据，由所有者节点返回，附带所有者地址
+async fn handler_get_meta(
     State(state): State<AppState>,
-    Path(key): Path<String>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
 ) -> Result<impl IntoResponse, AppError> {
-    let deleted_count = {
-        let target_addr = state.cluster.get_node_for_key(&key);
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
 
-        if target_addr == state.cluster.my_addr {
-            info!("Handling DELETE for key '{}' locally", key);
-            state.cache.delete(&key).await
-        } else {
-            info!("Forwarding DELETE for key '{}' to {}", key, target_addr);
-            state
-                .rpc_client
-                .forward_delete(&key, &target_addr)
-                .await
-                .unwrap_or(0)
+    let meta = if target_addr == state.cluster.my_addr {
+        info!("Handling META for key '{}' locally", key);
+        cache.meta(&key).await
+    } else {
+        info!("Forwarding META for key '{}' to {}", key, target_addr);
+        match state.rpc_client.forward_get_meta(&namespace, &key, &target_addr).await {
+            Ok(meta) => Some(meta),
+            Err(e) => match AppError::from(e) {
+                AppError::Cache(CacheError::KeyNotFound) => None,
+                err => return Err(err),
+            },
         }
     };
+    let meta = meta.ok_or(AppError::KeyNotFound)?;
 
-    Ok((StatusCode::OK, Json(deleted_count)))
+    Ok((StatusCode::OK, Json(MetaResponse { key, owner: target_addr, meta })))
 }
 
-fn parse_ttl_query(ttl_str: Option<String>) -> CacheItemTTL {
-    match ttl_str.as_deref() {
-        Some("permanent") => CacheItemTTL::Permanent,
-        Some(s) => {
-            if let Ok(sec) = s.parse::<u64>() {
-                CacheItemTTL::Custom(Duration::from_secs(sec))
-            } else {
-                CacheItemTTL::Default
-            }
+#[derive(Serialize)]
+struct MetaResponse {
+    key: String,
+    // 所有者节点的 RPC 地址
+    owner: String,
+    #[serde(flatten)]
+    meta: EntryMeta,
+}
+
+#[derive(Deserialize)]
+struct ExpireQuery {
+    ttl: Option<String>,
+    tti: Option<String>,
+}
+
+/// 只修改键的过期设置，`?ttl=` 或 `?tti=` 二选一
+async fn handler_expire(
+    state: State<AppState>,
+    path: Path<KeyPath>,
+    Query(query): Query<ExpireQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    if query.ttl.is_none() && query.tti.is_none() {
+        return Err(AppError::InvalidInput("One of 'ttl' and 'tti' is required".to_string()));
+    }
+    // 写入时无法解析的 ttl 按默认 TTL 处理，这里只修改过期设置，回退到默认值会悄悄改掉它
+    if let Some(ttl) = &query.ttl
+        && ttl != "permanent"
+        && ttl.parse::<u64>().is_err()
+    {
+        return Err(AppError::InvalidInput(format!("Invalid ttl '{}', expected seconds or 'permanent'", ttl)));
+    }
+    let ttl = parse_ttl_query(query.ttl, query.tti).map_err(AppError::InvalidInput)?;
+    change_expiry(state, path, ttl).await
+}
+
+/// 使键永不过期
+async fn handler_persist(
+    state: State<AppState>,
+    path: Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    change_expiry(state, path, CacheItemTTL::Permanent).await
+}
+
+async fn change_expiry(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    ttl: CacheItemTTL,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let version = if target_addr == state.cluster.my_addr {
+        info!("Handling EXPIRE for key '{}' ({:?}) locally", key, ttl);
+        cache.expire(&key, ttl).await?
+    } else {
+        info!("Forwarding EXPIRE for key '{}' ({:?}) to {}", key, ttl, target_addr);
+        state
+            .rpc_client
+            .forward_expire(&namespace, &key, ttl, &target_addr)
+            .await?
+    };
+    Ok((StatusCode::OK, [(header::ETAG, format_etag(version))]))
+}
+
+/// 键的过期方式和剩余存活时间（毫秒），永久的键为 null
+async fn handler_get_ttl(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let ttl = if target_addr == state.cluster.my_addr {
+        info!("Handling TTL for key '{}' locally", key);
+        cache.ttl(&key).await
+    } else {
+        info!("Forwarding TTL for key '{}' to {}", key, target_addr);
+        match state.rpc_client.forward_get_ttl(&namespace, &key, &target_addr).await {
+            Ok(ttl) => Some(ttl),
+            Err(e) => match AppError::from(e) {
+                AppError::Cache(CacheError::KeyNotFound) => None,
+                err => return Err(err),
+            },
         }
-        None => CacheItemTTL::Default,
+    };
+    let (ttl_mode, remaining) = ttl.ok_or(AppError::KeyNotFound)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "key": key,
+            "ttl_mode": ttl_mode,
+            "remaining_ttl_ms": remaining.map(|ttl| ttl.as_millis() as u64),
+        })),
+    ))
+}
+
+/// 数据结构的元素个数，键不存在时为 0
+async fn handler_len(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let len = if target_addr == state.cluster.my_addr {
+        info!("Handling LEN for key '{}' locally", key);
+        cache.len(&key).await?
+    } else {
+        info!("Forwarding LEN for key '{}' to {}", key, target_addr);
+        state.rpc_client.forward_len(&namespace, &key, &target_addr).await?
+    };
+
+    Ok((StatusCode::OK, Json(json!({ "key": key, "len": len }))))
+}
+
+/// 哈希的所有字段，以 `{field: value}` 返回
+async fn handler_hash_get_all(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let fields = if target_addr == state.cluster.my_addr {
+        info!("Handling HGETALL for key '{}' locally", key);
+        cache.hash_get_all(&key).await?
+    } else {
+        info!("Forwarding HGETALL for key '{}' to {}", key, target_addr);
+        match state.rpc_client.forward_hash_get_all(&namespace, &key, &target_addr).await {
+            Ok(fields) => Some(fields),
+            Err(e) => match AppError::from(e) {
+                AppError::Cache(CacheError::KeyNotFound) => None,
+                err => return Err(err),
+            },
+        }
+    };
+    let fields = fields.ok_or(AppError::KeyNotFound)?;
+
+    Ok((StatusCode::OK, Json(fields)))
+}
+
+async fn handler_hash_get(
+    State(state): State<AppState>,
+    Path(FieldPath { namespace, key, field }): Path<FieldPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let value = if target_addr == state.cluster.my_addr {
+        info!("Handling HGET for key '{}' field '{}' locally", key, field);
+        cache.hash_get(&key, &field).await?
+    } else {
+        info!("Forwarding HGET for key '{}' field '{}' to {}", key, field, target_addr);
+        state.rpc_client.forward_hash_get(&namespace, &key, &field, &target_addr).await?
+    };
+    let value = value.ok_or(AppError::KeyNotFound)?;
+
+    let mut result_map = Map::new();
+    result_map.insert(field, value);
+    Ok((StatusCode::OK, Json(json!(result_map))))
+}
+
+/// 写入数据结构时的查询参数，只在键不存在、需要创建时生效
+#[derive(Debug, Deserialize)]
+struct CreateQuery {
+    ttl: Option<String>,
+    tti: Option<String>,
+    jitter: Option<bool>,
+}
+
+impl CreateQuery {
+    fn ttl(self) -> Result<EntryTtl, AppError> {
+        let ttl = parse_ttl_query(self.ttl, self.tti).map_err(AppError::InvalidInput)?;
+        Ok(EntryTtl { ttl, jitter: self.jitter != Some(false) })
+    }
+}
+
+/// 以 JSON 请求体设置哈希的一个字段，新建字段时返回 201
+async fn handler_hash_set(
+    State(state): State<AppState>,
+    Path(FieldPath { namespace, key, field }): Path<FieldPath>,
+    Query(query): Query<CreateQuery>,
+    Json(value): Json<Value>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let ttl = query.ttl()?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let (created, version) = if target_addr == state.cluster.my_addr {
+        info!("Handling HSET for key '{}' field '{}' locally", key, field);
+        cache.hash_set(key, field, value, ttl).await?
+    } else {
+        info!("Forwarding HSET for key '{}' field '{}' to {}", key, field, target_addr);
+        state
+            .rpc_client
+            .forward_hash_set(&namespace, &key, &field, &value, ttl, &target_addr)
+            .await?
+    };
+
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, [(header::ETAG, format_etag(version))]))
+}
+
+/// 删除哈希的一个字段，返回删除的数量
+async fn handler_hash_delete(
+    State(state): State<AppState>,
+    Path(FieldPath { namespace, key, field }): Path<FieldPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let deleted = if target_addr == state.cluster.my_addr {
+        info!("Handling HDEL for key '{}' field '{}' locally", key, field);
+        cache.hash_delete(&key, &field).await?
+    } else {
+        info!("Forwarding HDEL for key '{}' field '{}' to {}", key, field, target_addr);
+        state
+            .rpc_client
+            .forward_hash_delete(&namespace, &key, &field, &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(deleted as i64)))
+}
+
+/// 列表操作的一端，未指定时压入尾部、从头部弹出
+#[derive(Debug, Deserialize)]
+struct EndQuery {
+    end: Option<ListEnd>,
+}
+
+/// 闭区间 `[start, stop]` 的下标，负数从末尾算起，默认为整个列表
+#[derive(Debug, Deserialize)]
+struct IndexQuery {
+    start: Option<i64>,
+    stop: Option<i64>,
+}
+
+/// 以 JSON 数组请求体压入列表，返回压入后的长度
+async fn handler_list_push(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<CreateQuery>,
+    Query(EndQuery { end }): Query<EndQuery>,
+    Json(values): Json<Vec<Value>>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let ttl = query.ttl()?;
+    let end = end.unwrap_or(ListEnd::Back);
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let (len, version) = if target_addr == state.cluster.my_addr {
+        info!("Handling PUSH of {} values for key '{}' locally", values.len(), key);
+        cache.list_push(key.clone(), values, end, ttl).await?
+    } else {
+        info!("Forwarding PUSH of {} values for key '{}' to {}", values.len(), key, target_addr);
+        state
+            .rpc_client
+            .forward_list_push(&namespace, &key, &values, end, ttl, &target_addr)
+            .await?
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, format_etag(version))],
+        Json(json!({ "key": key, "len": len })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct PopQuery {
+    end: Option<ListEnd>,
+    count: Option<usize>,
+}
+
+/// 从列表的一端弹出值，默认从头部弹出一个，返回弹出的值
+async fn handler_list_pop(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<PopQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let end = query.end.unwrap_or(ListEnd::Front);
+    let count = query.count.unwrap_or(1);
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let values = if target_addr == state.cluster.my_addr {
+        info!("Handling POP for key '{}' locally", key);
+        cache.list_pop(&key, end, count).await?
+    } else {
+        info!("Forwarding POP for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_list_pop(&namespace, &key, end, count, &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(values)))
+}
+
+async fn handler_list_range(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<IndexQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let (start, stop) = (query.start.unwrap_or(0), query.stop.unwrap_or(-1));
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let values = if target_addr == state.cluster.my_addr {
+        info!("Handling RANGE for key '{}' locally", key);
+        cache.list_range(&key, start, stop).await?
+    } else {
+        info!("Forwarding RANGE for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_list_range(&namespace, &key, start, stop, &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(values)))
+}
+
+/// 只保留列表的 `[start, stop]`，返回剩余的长度
+async fn handler_list_trim(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<IndexQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let (start, stop) = (query.start.unwrap_or(0), query.stop.unwrap_or(-1));
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let len = if target_addr == state.cluster.my_addr {
+        info!("Handling TRIM for key '{}' locally", key);
+        cache.list_trim(&key, start, stop).await?
+    } else {
+        info!("Forwarding TRIM for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_list_trim(&namespace, &key, start, stop, &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(json!({ "key": key, "len": len }))))
+}
+
+/// 以 JSON 字符串数组请求体向集合添加成员，返回新加入的数量
+async fn handler_set_add(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<CreateQuery>,
+    Json(members): Json<Vec<String>>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let ttl = query.ttl()?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let (added, version) = if target_addr == state.cluster.my_addr {
+        info!("Handling SADD of {} members for key '{}' locally", members.len(), key);
+        cache.set_add(key.clone(), members, ttl).await?
+    } else {
+        info!("Forwarding SADD of {} members for key '{}' to {}", members.len(), key, target_addr);
+        state
+            .rpc_client
+            .forward_set_add(&namespace, &key, members, ttl, &target_addr)
+            .await?
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, format_etag(version))],
+        Json(json!({ "key": key, "added": added })),
+    ))
+}
+
+/// 从集合移除一个成员，返回移除的数量
+async fn handler_set_remove(
+    State(state): State<AppState>,
+    Path(MemberPath { namespace, key, member }): Path<MemberPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let removed = if target_addr == state.cluster.my_addr {
+        info!("Handling SREM for key '{}' member '{}' locally", key, member);
+        cache.set_remove(&key, &[member]).await?
+    } else {
+        info!("Forwarding SREM for key '{}' member '{}' to {}", key, member, target_addr);
+        state
+            .rpc_client
+            .forward_set_remove(&namespace, &key, vec![member], &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(removed as i64)))
+}
+
+async fn handler_set_contains(
+    State(state): State<AppState>,
+    Path(MemberPath { namespace, key, member }): Path<MemberPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let is_member = if target_addr == state.cluster.my_addr {
+        info!("Handling SISMEMBER for key '{}' member '{}' locally", key, member);
+        cache.set_contains(&key, &member).await?
+    } else {
+        info!("Forwarding SISMEMBER for key '{}' member '{}' to {}", key, member, target_addr);
+        state
+            .rpc_client
+            .forward_set_contains(&namespace, &key, &member, &target_addr)
+            .await?
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "key": key, "member": member, "is_member": is_member })),
+    ))
+}
+
+async fn handler_set_members(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let members = if target_addr == state.cluster.my_addr {
+        info!("Handling SMEMBERS for key '{}' locally", key);
+        cache.set_members(&key).await?
+    } else {
+        info!("Forwarding SMEMBERS for key '{}' to {}", key, target_addr);
+        state.rpc_client.forward_set_members(&namespace, &key, &target_addr).await?
+    };
+
+    Ok((StatusCode::OK, Json(members)))
+}
+
+/// 以 `{member: score}` 请求体向有序集合添加成员或更新分数，返回新加入的数量
+async fn handler_zset_add(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<CreateQuery>,
+    Json(scores): Json<BTreeMap<String, f64>>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let ttl = query.ttl()?;
+    if scores.values().any(|score| !score.is_finite()) {
+        return Err(AppError::InvalidInput("Score must be a finite number".to_string()));
+    }
+    let members: Vec<(String, f64)> = scores.into_iter().collect();
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let (added, version) = if target_addr == state.cluster.my_addr {
+        info!("Handling ZADD of {} members for key '{}' locally", members.len(), key);
+        cache.zset_add(key.clone(), members, ttl).await?
+    } else {
+        info!("Forwarding ZADD of {} members for key '{}' to {}", members.len(), key, target_addr);
+        state
+            .rpc_client
+            .forward_zset_add(&namespace, &key, members, ttl, &target_addr)
+            .await?
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, format_etag(version))],
+        Json(json!({ "key": key, "added": added })),
+    ))
+}
+
+/// 从有序集合移除一个成员，返回移除的数量
+async fn handler_zset_remove(
+    State(state): State<AppState>,
+    Path(MemberPath { namespace, key, member }): Path<MemberPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let removed = if target_addr == state.cluster.my_addr {
+        info!("Handling ZREM for key '{}' member '{}' locally", key, member);
+        cache.zset_remove(&key, &[member]).await?
+    } else {
+        info!("Forwarding ZREM for key '{}' member '{}' to {}", key, member, target_addr);
+        state
+            .rpc_client
+            .forward_zset_remove(&namespace, &key, vec![member], &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(removed as i64)))
+}
+
+// 未指定 `?lease_ms=` 时的租约时长
+const DEFAULT_LOCK_LEASE_MS: u64 = 30_000;
+
+#[derive(Debug, Deserialize)]
+struct LockQuery {
+    // 持有者的名称，只用于查看是谁持有锁
+    holder: Option<String>,
+    lease_ms: Option<u64>,
+    // 续租和释放时必须给出获取锁时得到的令牌
+    token: Option<u64>,
+}
+
+impl LockQuery {
+    // 租约为 0 由 `CacheStore` 拒绝，转发到其他节点的请求同样检查
+    fn lease(&self) -> Duration {
+        Duration::from_millis(self.lease_ms.unwrap_or(DEFAULT_LOCK_LEASE_MS))
+    }
+
+    fn token(&self) -> Result<u64, AppError> {
+        self.token
+            .ok_or_else(|| AppError::InvalidInput("'token' is required".to_string()))
+    }
+}
+
+/// 获取锁，成功时返回防护令牌。锁已被持有时返回 409 及当前持有者。
+/// 令牌在同一个所有者节点上单调递增，受保护的资源应拒绝比见过的令牌更小的请求。
+async fn handler_lock_acquire(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<LockQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let lease = query.lease();
+    let holder = query.holder.unwrap_or_default();
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let token = if target_addr == state.cluster.my_addr {
+        info!("Handling LOCK for key '{}' by '{}' locally", key, holder);
+        cache.lock_acquire(key.clone(), holder.clone(), lease).await?
+    } else {
+        info!("Forwarding LOCK for key '{}' by '{}' to {}", key, holder, target_addr);
+        state
+            .rpc_client
+            .forward_lock_acquire(&namespace, key.clone(), holder.clone(), lease, &target_addr)
+            .await?
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "key": key, "holder": holder, "token": token, "lease_ms": lease.as_millis() as u64 })),
+    ))
+}
+
+/// 以令牌续租，租约从现在起重新计算
+async fn handler_lock_renew(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<LockQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let lease = query.lease();
+    let token = query.token()?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    if target_addr == state.cluster.my_addr {
+        info!("Handling LOCK RENEW for key '{}' locally", key);
+        cache.lock_renew(&key, token, lease).await?
+    } else {
+        info!("Forwarding LOCK RENEW for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_lock_renew(&namespace, &key, token, lease, &target_addr)
+            .await?
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "key": key, "token": token, "lease_ms": lease.as_millis() as u64 })),
+    ))
+}
+
+/// 以令牌释放锁
+async fn handler_lock_release(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<LockQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let token = query.token()?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    if target_addr == state.cluster.my_addr {
+        info!("Handling UNLOCK for key '{}' locally", key);
+        cache.lock_release(&key, token).await?
+    } else {
+        info!("Forwarding UNLOCK for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_lock_release(&namespace, &key, token, &target_addr)
+            .await?
+    };
+
+    Ok((StatusCode::OK, Json(json!({ "key": key, "released": true }))))
+}
+
+/// 锁的持有者、令牌和租约的剩余时间，未被持有时返回 404
+async fn handler_lock_info(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let info = if target_addr == state.cluster.my_addr {
+        info!("Handling LOCK INFO for key '{}' locally", key);
+        cache.lock_info(&key).await?
+    } else {
+        info!("Forwarding LOCK INFO for key '{}' to {}", key, target_addr);
+        state.rpc_client.forward_lock_info(&namespace, &key, &target_addr).await?
+    };
+    let (lock, remaining) = info.ok_or(AppError::KeyNotFound)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "key": key,
+            "holder": lock.holder,
+            "token": lock.token,
+            "remaining_ms": remaining.as_millis() as u64,
+        })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimitQuery {
+    // 每 `period_ms` 毫秒补满 `limit` 个令牌，`limit` 也是允许的突发量
+    limit: u64,
+    period_ms: u64,
+    cost: Option<u64>,
+}
+
+/// 检查并消耗键上令牌桶中的令牌，在所有者节点上原子地执行。
+/// 允许时返回 200，拒绝时返回 429 并带上 `Retry-After`（秒，向上取整）。
+async fn handler_rate_limit(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<RateLimitQuery>,
+) -> Result<Response, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let limit = RateLimit::new(query.limit, Duration::from_millis(query.period_ms), query.cost.unwrap_or(1))
+        .map_err(AppError::InvalidInput)?;
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let outcome = if target_addr == state.cluster.my_addr {
+        info!("Handling RATELIMIT for key '{}' locally", key);
+        cache.rate_limit(key.clone(), limit).await?
+    } else {
+        info!("Forwarding RATELIMIT for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_rate_limit(&namespace, key.clone(), limit, &target_addr)
+            .await?
+    };
+
+    let retry_after_ms = outcome.retry_after.as_millis() as u64;
+    let body = Json(json!({
+        "key": key,
+        "allowed": outcome.allowed,
+        "remaining": outcome.remaining,
+        "limit": limit.limit,
+        "retry_after_ms": retry_after_ms,
+    }));
+    if outcome.allowed {
+        return Ok((StatusCode::OK, body).into_response());
+    }
+    let retry_after_secs = retry_after_ms.div_ceil(1000).to_string();
+    Ok((StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, retry_after_secs)], body).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct RevQuery {
+    // 按分数从大到小排名，用于排行榜
+    rev: Option<bool>,
+}
+
+/// 成员的排名（从 0 开始）和分数，成员不存在时返回 404
+async fn handler_zset_rank(
+    State(state): State<AppState>,
+    Path(MemberPath { namespace, key, member }): Path<MemberPath>,
+    Query(RevQuery { rev }): Query<RevQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let rev = rev.unwrap_or(false);
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let rank = if target_addr == state.cluster.my_addr {
+        info!("Handling ZRANK for key '{}' member '{}' locally", key, member);
+        cache.zset_rank(&key, &member, rev).await?
+    } else {
+        info!("Forwarding ZRANK for key '{}' member '{}' to {}", key, member, target_addr);
+        state
+            .rpc_client
+            .forward_zset_rank(&namespace, &key, &member, rev, &target_addr)
+            .await?
+    };
+    let (rank, score) = rank.ok_or(AppError::KeyNotFound)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "key": key, "member": member, "rank": rank, "score": score })),
+    ))
+}
+
+/// 按排名（`start`/`stop`）或分数（`min`/`max`）读取有序集合，默认按排名读取全部
+#[derive(Debug, Deserialize)]
+struct ZsetRangeQuery {
+    start: Option<i64>,
+    stop: Option<i64>,
+    min: Option<f64>,
+    max: Option<f64>,
+    rev: Option<bool>,
+}
+
+async fn handler_zset_range(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<ZsetRangeQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let by_rank = query.start.is_some() || query.stop.is_some();
+    let by_score = query.min.is_some() || query.max.is_some();
+    let range = match (by_rank, by_score) {
+        (true, true) => {
+            return Err(AppError::InvalidInput(
+                "Only one of 'start'/'stop' and 'min'/'max' may be specified".to_string(),
+            ));
+        }
+        (_, true) => ZsetRange::Score {
+            min: query.min.unwrap_or(f64::NEG_INFINITY),
+            max: query.max.unwrap_or(f64::INFINITY),
+        },
+        _ => ZsetRange::Rank { start: query.start.unwrap_or(0), stop: query.stop.unwrap_or(-1) },
+    };
+    let rev = query.rev.unwrap_or(false);
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let members = if target_addr == state.cluster.my_addr {
+        info!("Handling ZRANGE for key '{}' locally", key);
+        cache.zset_range(&key, range, rev).await?
+    } else {
+        info!("Forwarding ZRANGE for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_zset_range(&namespace, &key, range, rev, &target_addr)
+            .await?
+    };
+
+    let members: Vec<Value> = members
+        .into_iter()
+        .map(|(member, score)| json!({ "member": member, "score": score }))
+        .collect();
+    Ok((StatusCode::OK, Json(members)))
+}
+
+async fn handler_delete(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let deleted_count = {
+        let target_addr = state.cluster.get_node_for_key(&key);
+
+        if target_addr == state.cluster.my_addr {
+            info!("Handling DELETE for key '{}' locally", key);
+            cache.delete(&key).await?
+        } else {
+            info!("Forwarding DELETE for key '{}' to {}", key, target_addr);
+            // 目标节点拒绝删除（例如键是锁）时如实返回，其他错误仍按未删除处理
+            match state.rpc_client.forward_delete(&namespace, &key, &target_addr).await {
+                Ok(count) => count,
+                Err(e) => match AppError::from(e) {
+                    AppError::Cache(e) => return Err(AppError::Cache(e)),
+                    _ => 0,
+                },
+            }
+        }
+    };
+
+    Ok((StatusCode::OK, Json(deleted_count)))
+}
+
+/// 在集群的每个节点上删除带有该标签的条目。
+/// 部分节点失败时返回 502，响应中列出失败的节点，已删除的数量仍然有效。
+async fn handler_invalidate_tag(
+    State(state): State<AppState>,
+    Path(TagPath { namespace, tag }): Path<TagPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = Arc::clone(state.namespaces.get(&namespace)?);
+    info!("Invalidating tag '{}' on all nodes", tag);
+
+    let mut tasks = JoinSet::new();
+    for node in state.cluster.nodes() {
+        let node = node.clone();
+        let tag = tag.clone();
+        if node == state.cluster.my_addr {
+            let cache = Arc::clone(&cache);
+            tasks.spawn(async move { (node, Ok(cache.invalidate_tag(&tag).await)) });
+        } else {
+            let rpc_client = state.rpc_client.clone();
+            let namespace = namespace.clone();
+            tasks.spawn(async move {
+                let result = rpc_client
+                    .forward_invalidate_tag(&namespace, &tag, &node)
+                    .await;
+                (node, result)
+            });
+        }
+    }
+
+    let mut deleted = 0;
+    let mut failed_nodes = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (node, result) = joined.map_err(|e| AppError::InternalError(e.to_string()))?;
+        match result {
+            Ok(count) => deleted += count,
+            Err(e) => {
+                warn!("Failed to invalidate tag '{}' on {}: {}", tag, node, e);
+                failed_nodes.push(node);
+            }
+        }
+    }
+
+    let status = if failed_nodes.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    Ok((
+        status,
+        Json(json!({ "deleted": deleted, "failed_nodes": failed_nodes })),
+    ))
+}
+
+const DEFAULT_LIST_LIMIT: usize = 100;
+const MAX_LIST_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct ListKeysQuery {
+    prefix: Option<String>,
+    // 上一页返回的 `next_cursor`
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+/// 向所有节点并发查询并合并结果，按字典序分页。
+/// 游标是本页最后一个键，下一页从它之后继续；`next_cursor` 为 null 表示已列完。
+async fn handler_list_keys(
+    State(state): State<AppState>,
+    ns_path: Option<Path<NamespacePath>>,
+    Query(query): Query<ListKeysQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = ns_path.and_then(|Path(p)| p.namespace).unwrap_or_default();
+    let cache = Arc::clone(state.namespaces.get(&namespace)?);
+    let prefix = query.prefix.unwrap_or_default();
+    let cursor = query.cursor.filter(|c| !c.is_empty());
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+
+    let mut tasks = JoinSet::new();
+    for node in state.cluster.nodes() {
+        let node = node.clone();
+        let prefix = prefix.clone();
+        let cursor = cursor.clone();
+        if node == state.cluster.my_addr {
+            let cache = Arc::clone(&cache);
+            tasks.spawn(async move { Ok(cache.keys(&prefix, cursor.as_deref(), limit)) });
+        } else {
+            let rpc_client = state.rpc_client.clone();
+            let namespace = namespace.clone();
+            tasks.spawn(async move {
+                rpc_client
+                    .forward_list_keys(&namespace, &prefix, cursor.as_deref(), limit, &node)
+                    .await
+            });
+        }
+    }
+
+    // 每个节点都返回了各自最小的 limit 个键，合并后取前 limit 个即为全局结果
+    let mut keys = BTreeSet::new();
+    let mut truncated = false;
+    while let Some(joined) = tasks.join_next().await {
+        let node_keys = joined.map_err(|e| AppError::InternalError(e.to_string()))??;
+        truncated |= node_keys.len() >= limit;
+        keys.extend(node_keys);
+    }
+    while keys.len() > limit {
+        keys.pop_last();
+        truncated = true;
+    }
+
+    let next_cursor = if truncated { keys.last().cloned() } else { None };
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "keys": keys, "next_cursor": next_cursor })),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionBody {
+    ops: Vec<TxnOpBody>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum TxnOpBody {
+    Set {
+        key: String,
+        value: Value,
+        // 同查询参数 `?ttl=` 与 `?tti=`，可以写成数字
+        ttl: Option<Value>,
+        tti: Option<Value>,
+        #[serde(default)]
+        tags: Vec<String>,
+        jitter: Option<bool>,
+    },
+    Delete {
+        key: String,
+    },
+    // 要求键的当前版本等于 `version`，0 表示要求键不存在
+    Compare {
+        key: String,
+        version: u64,
+    },
+}
+
+/// 在哈希标签相同的多个键上原子地执行一批 set、delete、compare，全部生效或全部不生效。
+/// 例如 `{user42}:profile` 与 `{user42}:session` 可以放在同一个事务里。
+async fn handler_transaction(
+    State(state): State<AppState>,
+    ns_path: Option<Path<NamespacePath>>,
+    Json(body): Json<TransactionBody>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = ns_path.and_then(|Path(p)| p.namespace).unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+
+    let ops = body
+        .ops
+        .into_iter()
+        .map(|op| {
+            Ok(match op {
+                TxnOpBody::Set { key, value, ttl, tti, tags, jitter } => {
+                    check_reserved_key(&key)?;
+                    let ttl = EntryTtl {
+                        ttl: parse_ttl_query(ttl.map(param_string), tti.map(param_string))
+                            .map_err(AppError::InvalidInput)?,
+                        jitter: jitter != Some(false),
+                    };
+                    TxnOp::Set { key, value: CacheValue::Json(value), ttl, tags }
+                }
+                TxnOpBody::Delete { key } => TxnOp::Delete { key },
+                TxnOpBody::Compare { key, version } => TxnOp::Compare { key, version },
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+    let Some(first) = ops.first() else {
+        return Err(AppError::InvalidInput("Transaction has no operations".to_string()));
+    };
+    if ops.iter().any(|op| hash_slot(op.key()) != hash_slot(first.key())) {
+        return Err(CacheError::CrossSlot.into());
+    }
+
+    let summary: Vec<(&'static str, String)> = ops
+        .iter()
+        .map(|op| {
+            let name = match op {
+                TxnOp::Set { .. } => "set",
+                TxnOp::Delete { .. } => "delete",
+                TxnOp::Compare { .. } => "compare",
+            };
+            (name, op.key().to_string())
+        })
+        .collect();
+    let target_addr = state.cluster.get_node_for_key(first.key());
+
+    let results = if target_addr == state.cluster.my_addr {
+        info!("Handling transaction of {} ops locally", ops.len());
+        cache.transaction(ops).await?
+    } else {
+        info!("Forwarding transaction of {} ops to {}", ops.len(), target_addr);
+        state.rpc_client.forward_transaction(&namespace, ops, &target_addr).await?
+    };
+
+    let results: Vec<Value> = summary
+        .into_iter()
+        .zip(results)
+        .map(|((op, key), result)| match op {
+            "delete" => json!({ "op": op, "key": key, "deleted": result > 0 }),
+            _ => json!({ "op": op, "key": key, "version": result }),
+        })
+        .collect();
+    Ok((StatusCode::OK, Json(json!({ "owner": target_addr, "results": results }))))
+}
+
+/// JSON 中的参数值按查询参数的写法取出，数字转为字符串
+fn param_string(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+// 每个 watch 连接缓存的待发送事件数
+const WATCH_BUFFER: usize = 256;
+// 与其他节点的 watch 流断开后的重连间隔，每次失败翻倍
+const WATCH_RETRY_MIN: Duration = Duration::from_millis(200);
+const WATCH_RETRY_MAX: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct WatchQuery {
+    prefix: Option<String>,
+}
+
+/// 以 Server-Sent Events 推送整个集群中键的变更（set、delete、expire、evict）。
+/// 本节点的变更直接订阅，其他节点的变更由各自的所有者通过 watch 流推送过来。
+async fn handler_watch(
+    State(state): State<AppState>,
+    ns_path: Option<Path<NamespacePath>>,
+    Query(query): Query<WatchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = ns_path.and_then(|Path(p)| p.namespace).unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let prefix = query.prefix.unwrap_or_default();
+
+    // 客户端断开后 rx 被丢弃，各个转发任务随之退出
+    let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+    for node in state.cluster.nodes() {
+        let node = node.clone();
+        let prefix = prefix.clone();
+        let tx = tx.clone();
+        if node == state.cluster.my_addr {
+            let events = cache.subscribe();
+            tokio::spawn(async move {
+                forward_events(events, &prefix, tx, |item| Ok(watch_sse_event(item, &node))).await;
+            });
+        } else {
+            let rpc_client = state.rpc_client.clone();
+            let namespace = namespace.clone();
+            tokio::spawn(relay_watch(rpc_client, namespace, prefix, node, tx));
+        }
+    }
+
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+/// 转发一个远端节点的变更事件，连接断开时退避重连，直到客户端断开
+async fn relay_watch(
+    rpc_client: RpcClient,
+    namespace: String,
+    prefix: String,
+    node: String,
+    tx: mpsc::Sender<Result<Event, Infallible>>,
+) {
+    let mut backoff = WATCH_RETRY_MIN;
+    let mut broken = false;
+    loop {
+        match rpc_client.forward_watch(&namespace, &prefix, &node).await {
+            Ok(mut stream) => {
+                backoff = WATCH_RETRY_MIN;
+                // 断线期间的事件无从补发，提示客户端重新读取
+                if std::mem::take(&mut broken) && tx.send(Ok(resync_sse_event(&node))).await.is_err() {
+                    return;
+                }
+                loop {
+                    let next = tokio::select! {
+                        _ = tx.closed() => return,
+                        next = stream.next() => next,
+                    };
+                    match next {
+                        Ok(Some(item)) => {
+                            if tx.send(Ok(watch_sse_event(item, &node))).await.is_err() {
+                                return;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            warn!("Watch stream from {} broke: {}", node, e);
+                            break;
+                        }
+                    }
+                }
+                broken = true;
+            }
+            Err(e) => {
+                warn!("Failed to watch {}: {}", node, e);
+                // 连接不上期间这个节点的事件同样丢失，连上之后也要提示
+                broken = true;
+            }
+        }
+        tokio::select! {
+            _ = tx.closed() => return,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+        backoff = (backoff * 2).min(WATCH_RETRY_MAX);
+    }
+}
+
+/// SSE 的事件名为变更种类，数据为 JSON；`lagged` 事件说明来自 `node` 的事件有丢失
+fn watch_sse_event(item: WatchItem, node: &str) -> Event {
+    match item {
+        WatchItem::Event(event) => Event::default()
+            .event(event.kind.as_str())
+            .data(json!(event).to_string()),
+        WatchItem::Lagged(missed) => Event::default()
+            .event("lagged")
+            .data(json!({ "node": node, "missed": missed }).to_string()),
+    }
+}
+
+/// 与 `node` 的订阅断开后重新建立时发送 `resync`，断开期间的事件已丢失
+fn resync_sse_event(node: &str) -> Event {
+    Event::default().event("resync").data(json!({ "node": node }).to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct IncrQuery {
+    by: Option<String>,
+    initial: Option<String>,
+    ttl: Option<String>,
+    tti: Option<String>,
+    jitter: Option<bool>,
+}
+
+async fn handler_incr(
+    state: State<AppState>,
+    path: Path<KeyPath>,
+    query: Query<IncrQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    increment(state, path, query, false).await
+}
+
+async fn handler_decr(
+    state: State<AppState>,
+    path: Path<KeyPath>,
+    query: Query<IncrQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    increment(state, path, query, true).await
+}
+
+async fn increment(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    Query(query): Query<IncrQuery>,
+    negate: bool,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let by = match query.by.as_deref() {
+        Some(s) => parse_number(s)?,
+        None => Number::from(1),
+    };
+    let delta = if negate { negate_number(&by)? } else { by };
+    let initial = match query.initial.as_deref() {
+        Some(s) => parse_number(s)?,
+        None => Number::from(0),
+    };
+    let ttl = EntryTtl {
+        ttl: parse_ttl_query(query.ttl, query.tti).map_err(AppError::InvalidInput)?,
+        jitter: query.jitter != Some(false),
+    };
+
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let (value, version) = if target_addr == state.cluster.my_addr {
+        info!("Handling INCR for key '{}' by {} locally", key, delta);
+        cache.increment(key.clone(), delta, initial, ttl).await?
+    } else {
+        info!("Forwarding INCR for key '{}' by {} to {}", key, delta, target_addr);
+        state
+            .rpc_client
+            .forward_increment(&namespace, key.clone(), &delta, &initial, ttl, &target_addr)
+            .await?
+    };
+
+    let mut result_map = Map::new();
+    result_map.insert(key, Value::Number(value));
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, format_etag(version))],
+        Json(json!(result_map)),
+    ))
+}
+
+fn parse_number(s: &str) -> Result<Number, AppError> {
+    serde_json::from_str::<Number>(s)
+        .map_err(|_| AppError::InvalidInput(format!("Invalid number '{}'", s)))
+}
+
+fn negate_number(n: &Number) -> Result<Number, AppError> {
+    let negated = match integer(n) {
+        Some(i) => number_from_i128(-i),
+        None => n.as_f64().and_then(|f| Number::from_f64(-f)),
+    };
+    negated.ok_or_else(|| AppError::InvalidInput(format!("Cannot negate '{}'", n)))
+}
+
+async fn handler_patch(
+    State(state): State<AppState>,
+    Path(KeyPath { namespace, key }): Path<KeyPath>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let namespace = namespace.unwrap_or_default();
+    let cache = state.namespaces.get(&namespace)?;
+    let patch = parse_patch_body(&headers, &body)?;
+
+    let target_addr = state.cluster.get_node_for_key(&key);
+
+    let (value, version) = if target_addr == state.cluster.my_addr {
+        info!("Handling PATCH for key '{}' locally", key);
+        cache.patch(&key, &patch).await?
+    } else {
+        info!("Forwarding PATCH for key '{}' to {}", key, target_addr);
+        state
+            .rpc_client
+            .forward_patch(&namespace, &key, &patch, &target_addr)
+            .await?
+    };
+
+    let mut result_map = Map::new();
+    result_map.insert(key, value);
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, format_etag(version))],
+        Json(json!(result_map)),
+    ))
+}
+
+/// 按 Content-Type 选择补丁格式：
+/// `application/merge-patch+json` 为 RFC 7396，`application/json-patch+json` 为 RFC 6902，
+/// 普通的 `application/json` 则按请求体是数组还是对象判断。
+fn parse_patch_body(headers: &HeaderMap, body: &[u8]) -> Result<JsonPatch, AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let doc: Value = serde_json::from_slice(body)?;
+    match content_type.as_str() {
+        "application/merge-patch+json" => Ok(JsonPatch::Merge(doc)),
+        "application/json-patch+json" => Ok(JsonPatch::Operations(serde_json::from_value(doc)?)),
+        "application/json" | "" if doc.is_array() => {
+            Ok(JsonPatch::Operations(serde_json::from_value(doc)?))
+        }
+        "application/json" | "" => Ok(JsonPatch::Merge(doc)),
+        other => Err(AppError::InvalidInput(format!(
+            "Unsupported patch content type '{}'",
+            other
+        ))),
+    }
+}
+
+/// 各命名空间的统计信息，按命名空间名称排序
+async fn handler_stats(State(state): State<AppState>) -> impl IntoResponse {
+    let stats: BTreeMap<_, _> = state
+        .namespaces
+        .iter()
+        .map(|(name, cache)| (name.clone(), cache.stats()))
+        .collect();
+    (StatusCode::OK, Json(stats))
+}
+
+/// 本节点写回队列的统计信息，未启用写回时没有这个资源，返回 404
+async fn handler_write_behind_stats(State(state): State<AppState>) -> Response {
+    match &state.write_behind {
+        Some(write_behind) => (StatusCode::OK, Json(write_behind.stats())).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Write-behind is not enabled" })),
+        )
+            .into_response(),
+    }
+}
+
+/// 解析逗号分隔的标签列表，忽略空白项与重复项
+fn parse_tags(tags: Option<&str>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for tag in tags.unwrap_or_default().split(',').map(str::trim) {
+        if !tag.is_empty() && !result.iter().any(|t| t == tag) {
+            result.push(tag.to_string());
+        }
+    }
+    result
+}
+
+/// 立即为本节点写一份快照
+async fn handler_snapshot(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let info = snapshot::take_snapshot(&state.settings.snapshot, &state.namespaces)
+        .await
+        .map_err(|e| AppError::InternalError(format!("Snapshot failed: {}", e)))?;
+    Ok((StatusCode::OK, Json(info)))
+}
+
+/// 立即重写本节点的追加日志
+async fn handler_compact_aof(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let aof = state
+        .aof
+        .as_ref()
+        .ok_or_else(|| AppError::InvalidInput("Append-only log is not enabled".to_string()))?;
+    let size = aof
+        .compact()
+        .await
+        .map_err(|e| AppError::InternalError(format!("Log compaction failed: {}", e)))?;
+    Ok((StatusCode::OK, Json(json!({ "size": size }))))
+}
+
+const DEFAULT_BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+fn is_json_content_type(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn format_etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// 解析条件写入的前置条件：
+/// `If-Match: "<version>"` 要求当前版本匹配，`If-None-Match: *` 要求键不存在。
+fn parse_precondition(headers: &HeaderMap) -> Result<Option<u64>, String> {
+    let if_match = headers.get(header::IF_MATCH);
+    let if_none_match = headers.get(header::IF_NONE_MATCH);
+
+    match (if_match, if_none_match) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => {
+            Err("Only one of 'If-Match' and 'If-None-Match' may be specified".to_string())
+        }
+        (Some(etag), None) => {
+            let etag = etag.to_str().map_err(|_| "Invalid If-Match header".to_string())?;
+            let version = etag.trim().trim_start_matches("W/").trim_matches('"');
+            version
+                .parse::<u64>()
+                .map(Some)
+                .map_err(|_| format!("Invalid If-Match version '{}'", etag))
+        }
+        (None, Some(etag)) if etag.as_bytes() == b"*" => Ok(Some(0)),
+        (None, Some(_)) => Err("Only 'If-None-Match: *' is supported".to_string()),
+    }
+}
+
+fn parse_ttl_query(
+    ttl_str: Option<String>,
+    tti_str: Option<String>,
+) -> Result<CacheItemTTL, String> {
+    if let Some(tti) = tti_str {
+        if ttl_str.is_some() {
+            return Err("Only one of 'ttl' and 'tti' may be specified".to_string());
+        }
+        let sec = tti
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid tti '{}', expected seconds", tti))?;
+        return Ok(CacheItemTTL::Idle(Duration::from_secs(sec)));
+    }
+
+    Ok(match ttl_str.as_deref() {
+        Some("permanent") => CacheItemTTL::Permanent,
+        Some(s) => {
+            if let Ok(sec) = s.parse::<u64>() {
+                CacheItemTTL::Custom(Duration::from_secs(sec))
+            } else {
+                CacheItemTTL::Default
+            }
+        }
+        None => CacheItemTTL::Default,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::Cluster;
+    use crate::config::{CacheSettings, Settings};
+    use crate::namespace::Namespaces;
+    use crate::rpc_server::run_rpc_server;
+
+    #[test]
+    fn test_reserved_keys_are_rejected() {
+        // 命名空间路由 `/ns/:namespace/...` 会遮住名为 ns 的键
+        for key in ["ns", "keys", "stats", "txn"] {
+            assert!(matches!(check_reserved_key(key), Err(AppError::InvalidInput(_))), "{}", key);
+        }
+        assert!(check_reserved_key("nsx").is_ok());
+        assert!(check_reserved_key("user:1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_relay_watch_resyncs_after_failed_connect() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let node = format!("http://127.0.0.1:{}", port);
+        let (tx, mut rx) = mpsc::channel(8);
+        tokio::spawn(relay_watch(RpcClient::new(100), String::new(), String::new(), node, tx));
+
+        // 第一次连接失败之后对端才启动，连上时要提示客户端重新读取
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut settings = Settings::for_tests(CacheSettings::for_tests());
+        settings.rpc_addr = format!("127.0.0.1:{}", port);
+        let settings = Arc::new(settings);
+        let namespaces = Arc::new(Namespaces::new(&settings));
+        let cluster = Arc::new(Cluster::new(&settings));
+        tokio::spawn(run_rpc_server(settings, namespaces, cluster));
+
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap().unwrap();
+        assert!(format!("{:?}", event).contains("event: resync"), "{:?}", event);
     }
 }
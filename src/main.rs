@@ -1,12 +1,18 @@
 // src/main.rs
 
+mod aof;
 mod cache;
 mod cluster;
 mod config;
 mod error;
 mod http_server;
+mod namespace;
+mod origin;
 mod rpc_client;
 mod rpc_server;
+mod snapshot;
+mod sorted_set;
+mod write_behind;
 mod logger;
 
 #[allow(unused_imports)]
@@ -14,8 +20,10 @@ use crate::{
     cache::{CacheStore, SharedCache},
     cluster::{Cluster, SharedCluster},
     config::{Settings, SharedSettings},
+    namespace::{Namespaces, SharedNamespaces},
 };
 use std::sync::Arc;
+use std::time::Duration;
 use log::{info, error, debug};
 
 
@@ -38,26 +46,72 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     info!("Initialized logger with level: [{}]", settings.log_level);
     debug!("Settings: {:?}", settings);
 
-    let cache: SharedCache = Arc::new(CacheStore::new(
-        settings.cache.capacity,
-        settings.cache.default_ttl_seconds,
-    ));
+    let namespaces: SharedNamespaces = Arc::new(Namespaces::new(&settings));
     info!("Cache store initialized.");
 
+    // 在服务开始接收请求之前加载快照
+    if settings.snapshot.restore_on_boot {
+        match snapshot::restore_latest(&settings.snapshot, &namespaces).await {
+            Ok(Some(restored)) => info!(
+                "Loaded snapshot {} ({} entries).",
+                restored.path.display(),
+                restored.entries
+            ),
+            Ok(None) => info!("No snapshot found in '{}'.", settings.snapshot.dir),
+            Err(e) => error!("Failed to load snapshot: {}", e),
+        }
+    }
+    snapshot::spawn_periodic(Arc::clone(&settings), Arc::clone(&namespaces));
+
+    // 重放追加日志之后才开始记录新的写入
+    let aof = if settings.aof.enabled {
+        if let Err(e) = aof::replay(&settings.aof, &namespaces).await {
+            error!("Failed to replay append-only log: {}", e);
+            return Err(e.into());
+        }
+        Some(aof::Aof::start(&settings.aof, &settings.snapshot, Arc::clone(&namespaces))?)
+    } else {
+        None
+    };
+
+    // 恢复的数据已在后端存储中，之后的客户端写入才需要写回
+    let write_behind = if settings.write_behind.enabled {
+        Some(write_behind::WriteBehind::open(&settings.write_behind, &namespaces)?)
+    } else {
+        None
+    };
+
+    let namespaces_cleanup = Arc::clone(&namespaces);
+    let cleanup_interval = Duration::from_millis(settings.cache.cleanup_interval_ms);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(cleanup_interval);
+        loop {
+            ticker.tick().await;
+            for (name, cache) in namespaces_cleanup.iter() {
+                cache.run_pending_tasks().await;
+                debug!("Cache cleanup done for '{}', {} live entries", name, cache.entry_count());
+            }
+        }
+    });
+    info!("Spawned cache cleanup task.");
+
     let cluster: SharedCluster = Arc::new(Cluster::new(&settings));
     info!("Cluster ring initialized.");
 
     let settings_rpc = Arc::clone(&settings);
-    let cache_rpc = Arc::clone(&cache);
-    let rpc_handle =
-        tokio::spawn(async move { rpc_server::run_rpc_server(settings_rpc, cache_rpc).await });
+    let namespaces_rpc = Arc::clone(&namespaces);
+    let cluster_rpc = Arc::clone(&cluster);
+    let rpc_handle = tokio::spawn(async move {
+        rpc_server::run_rpc_server(settings_rpc, namespaces_rpc, cluster_rpc).await
+    });
     info!("Spawned gRPC server task.");
 
     let settings_http = Arc::clone(&settings);
-    let cache_http = Arc::clone(&cache);
+    let namespaces_http = Arc::clone(&namespaces);
     let cluster_http = Arc::clone(&cluster);
     let http_handle = tokio::spawn(async move {
-        http_server::run_http_server(settings_http, cache_http, cluster_http).await
+        http_server::run_http_server(settings_http, namespaces_http, cluster_http, aof, write_behind)
+            .await
     });
     info!("Spawned HTTP server task.");
 
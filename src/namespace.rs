@@ -0,0 +1,131 @@
+// src/namespace.rs
+
+use crate::cache::{CacheStore, SharedCache};
+use crate::config::{NamespaceSettings, Settings};
+use crate::error::CacheError;
+use crate::origin::Origins;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 未指定命名空间的请求（包括原有的 `/:key` 路由）使用的命名空间
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// 本节点上所有命名空间的缓存。
+/// 每个命名空间拥有独立的 `CacheStore`，容量、默认 TTL 与驱逐互不影响。
+pub struct Namespaces {
+    caches: HashMap<String, SharedCache>,
+}
+
+pub type SharedNamespaces = Arc<Namespaces>;
+
+impl Namespaces {
+    pub fn new(settings: &Settings) -> Self {
+        let mut caches = HashMap::new();
+
+        let default_settings = settings
+            .namespaces
+            .get(DEFAULT_NAMESPACE)
+            .cloned()
+            .unwrap_or_default()
+            .resolve(&settings.cache);
+        caches.insert(
+            DEFAULT_NAMESPACE.to_string(),
+            Arc::new(
+                CacheStore::new(&default_settings)
+                    .with_origins(Origins::for_namespace(settings, DEFAULT_NAMESPACE)),
+            ),
+        );
+
+        for (name, ns_settings) in &settings.namespaces {
+            if name == DEFAULT_NAMESPACE {
+                continue;
+            }
+            let cache_settings = NamespaceSettings::resolve(ns_settings, &settings.cache);
+            let cache = CacheStore::new(&cache_settings)
+                .with_origins(Origins::for_namespace(settings, name));
+            caches.insert(name.clone(), Arc::new(cache));
+        }
+
+        for (name, origin) in &settings.origins {
+            if let Some(namespace) = &origin.namespace
+                && !caches.contains_key(namespace)
+            {
+                warn!("Origin '{}' refers to unknown namespace '{}'", name, namespace);
+            }
+        }
+
+        Self { caches }
+    }
+
+    /// 按名称取得命名空间的缓存，空字符串表示默认命名空间
+    pub fn get(&self, namespace: &str) -> Result<&SharedCache, CacheError> {
+        let name = if namespace.is_empty() { DEFAULT_NAMESPACE } else { namespace };
+        self.caches
+            .get(name)
+            .ok_or_else(|| CacheError::UnknownNamespace(name.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SharedCache)> {
+        self.caches.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheItemTTL;
+    use crate::config::{CacheSettings, EvictionPolicy};
+    use serde_json::json;
+
+    fn test_settings() -> Settings {
+        let mut namespaces = HashMap::new();
+        namespaces.insert(
+            "orders".to_string(),
+            NamespaceSettings {
+                capacity: Some(2),
+                eviction_policy: Some(EvictionPolicy::None),
+                ..Default::default()
+            },
+        );
+        let mut settings = Settings::for_tests(CacheSettings::for_tests());
+        settings.namespaces = namespaces;
+        settings
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_are_isolated() {
+        let namespaces = Namespaces::new(&test_settings());
+        let default = namespaces.get("").unwrap();
+        let orders = namespaces.get("orders").unwrap();
+
+        // 同名键在不同命名空间互不可见
+        default.set("k".to_string(), json!("d"), CacheItemTTL::Default, vec![]).await.unwrap();
+        orders.set("k".to_string(), json!("o"), CacheItemTTL::Default, vec![]).await.unwrap();
+        assert_eq!(default.get_versioned("k").await.unwrap().0, json!("d").into());
+        assert_eq!(orders.get_versioned("k").await.unwrap().0, json!("o").into());
+
+        // orders 写满只影响自身，默认命名空间仍可写入
+        orders.set("k2".to_string(), json!(2), CacheItemTTL::Default, vec![]).await.unwrap();
+        assert!(matches!(
+            orders.set("k3".to_string(), json!(3), CacheItemTTL::Default, vec![]).await,
+            Err(CacheError::CacheFull)
+        ));
+        default.set("k3".to_string(), json!(3), CacheItemTTL::Default, vec![]).await.unwrap();
+        assert_eq!(orders.stats().max_capacity, 2);
+        assert_eq!(default.stats().max_capacity, 100);
+
+        assert_eq!(default.delete("k").await.unwrap(), 1);
+        assert!(orders.get_versioned("k").await.is_some());
+    }
+
+    #[test]
+    fn test_unknown_namespace() {
+        let namespaces = Namespaces::new(&test_settings());
+        assert!(namespaces.get(DEFAULT_NAMESPACE).is_ok());
+        assert!(matches!(
+            namespaces.get("missing"),
+            Err(CacheError::UnknownNamespace(name)) if name == "missing"
+        ));
+    }
+}
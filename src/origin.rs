@@ -0,0 +1,292 @@
+// src/origin.rs
+
+use crate::cache::{CacheItemTTL, CacheValue, Grace};
+use crate::config::{OriginSettings, Settings};
+use crate::error::CacheError;
+use crate::namespace::DEFAULT_NAMESPACE;
+use axum::http::{StatusCode, header};
+use log::info;
+use std::time::Duration;
+
+/// 一个回源配置：键以 `prefix` 开头时从 `url` 加载
+#[derive(Debug)]
+pub struct Origin {
+    prefix: String,
+    url: String,
+    ttl: CacheItemTTL,
+    grace: Grace,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl Origin {
+    fn new(settings: &OriginSettings) -> Self {
+        let ttl = match settings.ttl_seconds {
+            Some(sec) => CacheItemTTL::Custom(Duration::from_secs(sec)),
+            None => CacheItemTTL::Default,
+        };
+        let grace = Grace {
+            revalidate: Duration::from_secs(settings.stale_while_revalidate_seconds),
+            if_error: Duration::from_secs(settings.stale_if_error_seconds),
+        };
+        Self {
+            prefix: settings.prefix.clone(),
+            url: settings.url.clone(),
+            ttl,
+            grace,
+            timeout: Duration::from_millis(settings.timeout_ms),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// 加载的值使用的 TTL
+    pub fn ttl(&self) -> CacheItemTTL {
+        self.ttl
+    }
+
+    /// 从该源加载的键过期后的宽限期
+    pub fn grace(&self) -> Grace {
+        self.grace
+    }
+
+    fn url_for(&self, key: &str) -> String {
+        let key = encode_key(key);
+        if self.url.contains("{key}") {
+            self.url.replace("{key}", &key)
+        } else {
+            format!("{}/{}", self.url.trim_end_matches('/'), key)
+        }
+    }
+
+    /// 从源加载一个键，源返回 404 时为 `None`。
+    /// `application/json` 的响应按 JSON 保存，其余按二进制保存并保留 Content-Type。
+    pub async fn fetch(&self, key: &str) -> Result<Option<CacheValue>, CacheError> {
+        let url = self.url_for(key);
+        info!("Loading key '{}' from origin {}", key, url);
+
+        let response = self
+            .client
+            .get(&url)
+            // 超时设在每个请求上，不依赖客户端构建是否成功
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| CacheError::OriginFailed(e.to_string()))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(CacheError::OriginFailed(format!("{} returned {}", url, status)));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| CacheError::OriginFailed(e.to_string()))?;
+
+        let is_json = content_type
+            .split(';')
+            .next()
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+        if is_json {
+            let value = serde_json::from_slice(&body).map_err(|e| {
+                CacheError::OriginFailed(format!("{} returned invalid JSON: {}", url, e))
+            })?;
+            Ok(Some(CacheValue::Json(value)))
+        } else {
+            Ok(Some(CacheValue::Binary { content_type, data: body }))
+        }
+    }
+}
+
+/// 一个命名空间的全部回源配置
+#[derive(Debug, Default)]
+pub struct Origins {
+    // 按前缀长度降序，最长的前缀优先匹配
+    origins: Vec<Origin>,
+}
+
+impl Origins {
+    pub fn for_namespace(settings: &Settings, namespace: &str) -> Self {
+        let mut origins: Vec<Origin> = settings
+            .origins
+            .values()
+            .filter(|o| o.namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE) == namespace)
+            .map(Origin::new)
+            .collect();
+        origins.sort_by_key(|o| std::cmp::Reverse(o.prefix.len()));
+        Self { origins }
+    }
+
+    pub fn find(&self, key: &str) -> Option<&Origin> {
+        self.origins.iter().find(|o| key.starts_with(&o.prefix))
+    }
+}
+
+/// 对键做百分号编码，使其可以放进 URL 路径
+fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheStore, Freshness};
+    use crate::config::CacheSettings;
+    use axum::Router;
+    use axum::extract::{Path, State};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // 模拟源：记录请求次数，延迟响应以便并发请求在加载期间到达
+    #[derive(Default)]
+    struct Stub {
+        hits: AtomicUsize,
+        down: AtomicBool,
+    }
+
+    async fn stub_origin(State(stub): State<Arc<Stub>>, Path(key): Path<String>) -> Response {
+        let hits = stub.hits.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        if stub.down.load(Ordering::SeqCst) {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        match key.as_str() {
+            "user:1" => ([(header::CONTENT_TYPE, "application/json")], r#"{"name":"alice"}"#)
+                .into_response(),
+            "user:hits" => ([(header::CONTENT_TYPE, "application/json")], hits.to_string())
+                .into_response(),
+            "user:avatar" => ([(header::CONTENT_TYPE, "image/png")], vec![0x89u8, b'P']).into_response(),
+            "user:broken" => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            _ => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    async fn test_cache(
+        ttl_seconds: u64,
+        stale_while_revalidate_seconds: u64,
+        stale_if_error_seconds: u64,
+    ) -> (CacheStore, Arc<Stub>) {
+        let stub = Arc::new(Stub::default());
+        let app = Router::new()
+            .route("/users/:key", get(stub_origin))
+            .with_state(Arc::clone(&stub));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let cache_settings = CacheSettings::for_tests();
+        let mut settings = Settings::for_tests(cache_settings.clone());
+        settings.origins.insert(
+            "users".to_string(),
+            OriginSettings {
+                namespace: None,
+                prefix: "user:".to_string(),
+                url: format!("http://{}/users/{{key}}", addr),
+                ttl_seconds: Some(ttl_seconds),
+                timeout_ms: 5000,
+                stale_while_revalidate_seconds,
+                stale_if_error_seconds,
+            },
+        );
+        let cache = CacheStore::new(&cache_settings)
+            .with_origins(Origins::for_namespace(&settings, DEFAULT_NAMESPACE));
+        (cache, stub)
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_load_once() {
+        let (cache, stub) = test_cache(30, 0, 0).await;
+        let cache = Arc::new(cache);
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..10 {
+            let cache = Arc::clone(&cache);
+            tasks.spawn(async move { cache.get_or_load("user:1").await });
+        }
+        while let Some(result) = tasks.join_next().await {
+            let (value, _, _) = result.unwrap().unwrap().unwrap();
+            assert_eq!(value, CacheValue::Json(serde_json::json!({"name": "alice"})));
+        }
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 1);
+
+        // 已缓存，不再回源
+        assert!(cache.get_or_load("user:1").await.unwrap().is_some());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_origin_responses() {
+        let (cache, stub) = test_cache(30, 0, 0).await;
+
+        let (value, _, _) = cache.get_or_load("user:avatar").await.unwrap().unwrap();
+        assert_eq!(
+            value,
+            CacheValue::Binary {
+                content_type: "image/png".to_string(),
+                data: vec![0x89u8, b'P'].into(),
+            }
+        );
+        assert!(cache.get_or_load("user:missing").await.unwrap().is_none());
+        assert!(matches!(
+            cache.get_or_load("user:broken").await,
+            Err(CacheError::OriginFailed(_))
+        ));
+        // 没有匹配的前缀时不回源
+        assert!(cache.get_or_load("order:1").await.unwrap().is_none());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate_and_if_error() {
+        let (cache, stub) = test_cache(1, 1, 2).await;
+        let read = |cache: &CacheStore| {
+            let cache = cache.clone();
+            async move {
+                let (value, _, freshness) = cache.get_or_load("user:hits").await.unwrap().unwrap();
+                (value, freshness)
+            }
+        };
+        let hits = |n: u64| CacheValue::Json(serde_json::json!(n));
+
+        assert_eq!(read(&cache).await, (hits(1), Freshness::Fresh));
+
+        // 过期后先返回旧值，后台刷新完成后返回新值
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(read(&cache).await, (hits(1), Freshness::Stale));
+        assert!(cache.get_versioned("user:hits").await.is_none());
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(read(&cache).await, (hits(2), Freshness::Fresh));
+
+        // 源不可用：宽限期内仍返回旧值，超过 stale-if-error 期限后报错
+        stub.down.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(read(&cache).await, (hits(2), Freshness::Stale));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert_eq!(read(&cache).await, (hits(2), Freshness::StaleIfError));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(matches!(
+            cache.get_or_load("user:hits").await,
+            Err(CacheError::OriginFailed(_))
+        ));
+    }
+}
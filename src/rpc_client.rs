@@ -1,11 +1,15 @@
 // src/rpc_client.rs
 
-use crate::cache::CacheItemTTL;
+use crate::cache::{
+    CacheItemTTL, CacheValue, EntryMeta, EntryTtl, Freshness, HashFields, JsonPatch, KeyEvent, KeyEventKind, ListEnd,
+    LockState, RateLimit, RateLimitOutcome, TtlMode, TxnOp, WatchItem, ZsetRange,
+};
 use crate::error::RpcClientError;
 use dashmap::DashMap;
-use serde_json::Value;
+use serde_json::{Number, Value};
 use std::sync::Arc;
 use std::time::Duration;
+use tonic::{Status, Streaming};
 use tonic::transport::{Channel, Endpoint};
 use log::{warn, info};
 
@@ -16,7 +20,13 @@ pub mod proto_cache {
 use proto_cache::{
     cache_service_client::CacheServiceClient, 
     set_request::TtlOption, 
-    DeleteRequest, GetRequest, SetRequest,
+    BinaryValue, CompareAndSetRequest, DeleteRequest, GetRequest, IncrementRequest, InvalidateTagRequest,
+    ListKeysRequest, PatchRequest, SetRequest,
+    TtlSpec, patch_request, ttl_spec, Freshness as ProtoFreshness, TtlMode as ProtoTtlMode,
+    ExpireRequest, HashFieldRequest, HashSetRequest, ListPushRequest, ListPopRequest, RangeRequest,
+    MembersRequest, MemberRequest, ScoredMember, ZsetAddRequest, ZsetRankRequest, ZsetRangeRequest,
+    zset_range_request, WatchRequest, WatchEvent, TransactionRequest, TxnCompare, txn_op,
+    LockAcquireRequest, LockRequest, RateLimitRequest,
 };
 
 #[derive(Debug, Clone)]
@@ -53,74 +63,937 @@ impl RpcClient {
         Ok(client)
     }
 
-    pub async fn forward_get(&self, key: &str, target_addr: &str) -> Result<Value, RpcClientError> {
+    /// 调用失败时记录日志；目标节点不可用时从连接池中移除它的连接
+    fn handle_status(&self, op: &str, target_addr: &str, status: tonic::Status) -> RpcClientError {
+        warn!("gRPC {} to {} failed: {}", op, target_addr, status);
+        if status.code() == tonic::Code::Unavailable {
+            self.pool.remove(target_addr);
+        }
+        status.into()
+    }
+
+    pub async fn forward_get(&self, namespace: &str, key: &str, target_addr: &str) -> Result<(CacheValue, u64, Freshness), RpcClientError> {
         let mut client = self.get_client(target_addr).await?;
         
         let request = tonic::Request::new(GetRequest {
             key: key.to_string(),
+            namespace: namespace.to_string(),
         });
 
         match client.internal_get(request).await {
             Ok(response) => {
-                let value_json = response.into_inner().value_json;
-                let value: Value = serde_json::from_str(&value_json)?;
-                Ok(value)
-            }
-            Err(status) => {
-                warn!("gRPC forward_get failed for key {}: {}", key, status);
-                if status.code() == tonic::Code::Unavailable {
-                    self.pool.remove(target_addr);
-                }
-                Err(status.into()) 
+                let response = response.into_inner();
+                let freshness = match response.freshness() {
+                    ProtoFreshness::Fresh => Freshness::Fresh,
+                    ProtoFreshness::Stale => Freshness::Stale,
+                    ProtoFreshness::StaleIfError => Freshness::StaleIfError,
+                };
+                let value = match response.binary_value {
+                    Some(binary) => CacheValue::Binary {
+                        content_type: binary.content_type,
+                        data: binary.data.into(),
+                    },
+                    None => CacheValue::from_json(
+                        serde_json::from_str(&response.value_json)?,
+                        &response.data_type,
+                    )
+                    .ok_or_else(|| Status::internal("Invalid data type"))?,
+                };
+                Ok((value, response.version, freshness))
             }
+            Err(status) => Err(self.handle_status("forward_get", target_addr, status)),
         }
     }
 
-    pub async fn forward_set(&self, key: String, value: Value, ttl: CacheItemTTL, target_addr: &str) -> Result<(), RpcClientError> {
+    pub async fn forward_set(
+        &self,
+        namespace: &str,
+        key: String,
+        value: CacheValue,
+        ttl: EntryTtl,
+        tags: Vec<String>,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
         let mut client = self.get_client(target_addr).await?;
 
-        let ttl_option = match ttl {
-            CacheItemTTL::Default => TtlOption::UseDefaultTtl(true),
-            CacheItemTTL::Permanent => TtlOption::SetPermanent(true),
-            CacheItemTTL::Custom(d) => TtlOption::SpecificTtlSeconds(d.as_secs()),
-        };
+        let request = tonic::Request::new(build_set_request(namespace, key, &value, ttl, tags)?);
+
+        match client.internal_set(request).await {
+            Ok(response) => Ok(response.into_inner().version),
+            Err(status) => Err(self.handle_status("forward_set", target_addr, status)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn forward_compare_and_set(
+        &self,
+        namespace: &str,
+        key: String,
+        value: CacheValue,
+        ttl: EntryTtl,
+        tags: Vec<String>,
+        expected_version: u64,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(CompareAndSetRequest {
+            set: Some(build_set_request(namespace, key, &value, ttl, tags)?),
+            expected_version,
+        });
 
-        let value_json = serde_json::to_string(&value)?;
+        match client.internal_compare_and_set(request).await {
+            Ok(response) => Ok(response.into_inner().version),
+            Err(status) => Err(self.handle_status("forward_compare_and_set", target_addr, status)),
+        }
+    }
 
-        let request = tonic::Request::new(SetRequest {
+    pub async fn forward_increment(
+        &self,
+        namespace: &str,
+        key: String,
+        delta: &Number,
+        initial: &Number,
+        ttl: EntryTtl,
+        target_addr: &str,
+    ) -> Result<(Number, u64), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(IncrementRequest {
             key,
-            value_json,
-            ttl_option: Some(ttl_option),
+            delta_json: serde_json::to_string(delta)?,
+            initial_json: serde_json::to_string(initial)?,
+            ttl: Some(ttl_to_spec(ttl)),
+            namespace: namespace.to_string(),
         });
 
-        match client.internal_set(request).await {
-            Ok(_) => Ok(()),
-            Err(status) => {
-                warn!("gRPC forward_set failed: {}", status);
-                if status.code() == tonic::Code::Unavailable {
-                    self.pool.remove(target_addr);
-                }
-                Err(status.into())
+        match client.internal_increment(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                let value: Number = serde_json::from_str(&response.value_json)?;
+                Ok((value, response.version))
+            }
+            Err(status) => Err(self.handle_status("forward_increment", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_patch(
+        &self,
+        namespace: &str,
+        key: &str,
+        patch: &JsonPatch,
+        target_addr: &str,
+    ) -> Result<(Value, u64), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let patch = match patch {
+            JsonPatch::Merge(doc) => patch_request::Patch::MergePatchJson(serde_json::to_string(doc)?),
+            JsonPatch::Operations(ops) => patch_request::Patch::JsonPatchJson(serde_json::to_string(ops)?),
+        };
+        let request = tonic::Request::new(PatchRequest {
+            key: key.to_string(),
+            patch: Some(patch),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_patch(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                let value: Value = serde_json::from_str(&response.value_json)?;
+                Ok((value, response.version))
             }
+            Err(status) => Err(self.handle_status("forward_patch", target_addr, status)),
         }
     }
 
-    pub async fn forward_delete(&self, key: &str, target_addr: &str) -> Result<i64, RpcClientError> {
+    pub async fn forward_delete(&self, namespace: &str, key: &str, target_addr: &str) -> Result<i64, RpcClientError> {
         let mut client = self.get_client(target_addr).await?;
 
         let request = tonic::Request::new(DeleteRequest {
             key: key.to_string(),
+            namespace: namespace.to_string(),
         });
 
         match client.internal_delete(request).await {
             Ok(response) => Ok(response.into_inner().deleted_count),
-            Err(status) => {
-                warn!("gRPC forward_delete failed for key {}: {}", key, status);
-                if status.code() == tonic::Code::Unavailable {
-                    self.pool.remove(target_addr);
+            Err(status) => Err(self.handle_status("forward_delete", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_invalidate_tag(&self, namespace: &str, tag: &str, target_addr: &str) -> Result<i64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(InvalidateTagRequest {
+            tag: tag.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_invalidate_tag(request).await {
+            Ok(response) => Ok(response.into_inner().deleted_count),
+            Err(status) => Err(self.handle_status("forward_invalidate_tag", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_list_keys(
+        &self,
+        namespace: &str,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: usize,
+        target_addr: &str,
+    ) -> Result<Vec<String>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(ListKeysRequest {
+            prefix: prefix.to_string(),
+            cursor: cursor.unwrap_or_default().to_string(),
+            limit: u32::try_from(limit).unwrap_or(u32::MAX),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_list_keys(request).await {
+            Ok(response) => Ok(response.into_inner().keys),
+            Err(status) => Err(self.handle_status("forward_list_keys", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_get_meta(
+        &self,
+        namespace: &str,
+        key: &str,
+        target_addr: &str,
+    ) -> Result<EntryMeta, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(GetRequest {
+            key: key.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_get_meta(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                let ttl_mode = ttl_mode_from_proto(response.ttl_mode());
+                Ok(EntryMeta {
+                    created_at_ms: response.created_at_ms,
+                    updated_at_ms: response.updated_at_ms,
+                    last_access_ms: (response.last_access_ms != 0).then_some(response.last_access_ms),
+                    access_count: response.access_count,
+                    ttl_mode,
+                    remaining_ttl_ms: (ttl_mode != TtlMode::Permanent)
+                        .then_some(response.remaining_ttl_ms),
+                    expires_at_ms: (ttl_mode != TtlMode::Permanent)
+                        .then_some(response.expires_at_ms),
+                    stale: response.stale,
+                    size: response.size,
+                    version: response.version,
+                    tags: response.tags,
+                })
+            }
+            Err(status) => Err(self.handle_status("forward_get_meta", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_expire(
+        &self,
+        namespace: &str,
+        key: &str,
+        ttl: CacheItemTTL,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(ExpireRequest {
+            key: key.to_string(),
+            ttl: Some(ttl_to_spec(ttl.into())),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_expire(request).await {
+            Ok(response) => Ok(response.into_inner().version),
+            Err(status) => Err(self.handle_status("forward_expire", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_get_ttl(
+        &self,
+        namespace: &str,
+        key: &str,
+        target_addr: &str,
+    ) -> Result<(TtlMode, Option<Duration>), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(GetRequest {
+            key: key.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_get_ttl(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                let ttl_mode = ttl_mode_from_proto(response.ttl_mode());
+                let remaining = (ttl_mode != TtlMode::Permanent)
+                    .then(|| Duration::from_millis(response.remaining_ttl_ms));
+                Ok((ttl_mode, remaining))
+            }
+            Err(status) => Err(self.handle_status("forward_get_ttl", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_hash_get(
+        &self,
+        namespace: &str,
+        key: &str,
+        field: &str,
+        target_addr: &str,
+    ) -> Result<Option<Value>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(HashFieldRequest {
+            key: key.to_string(),
+            field: field.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_hash_get(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                if !response.found {
+                    return Ok(None);
                 }
-                Err(status.into())
+                Ok(Some(serde_json::from_str(&response.value_json)?))
+            }
+            Err(status) => Err(self.handle_status("forward_hash_get", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_hash_get_all(
+        &self,
+        namespace: &str,
+        key: &str,
+        target_addr: &str,
+    ) -> Result<HashFields, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(GetRequest {
+            key: key.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_hash_get_all(request).await {
+            Ok(response) => response
+                .into_inner()
+                .fields
+                .into_iter()
+                .map(|(field, value_json)| Ok((field, serde_json::from_str(&value_json)?)))
+                .collect(),
+            Err(status) => Err(self.handle_status("forward_hash_get_all", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_hash_set(
+        &self,
+        namespace: &str,
+        key: &str,
+        field: &str,
+        value: &Value,
+        ttl: EntryTtl,
+        target_addr: &str,
+    ) -> Result<(bool, u64), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(HashSetRequest {
+            key: key.to_string(),
+            field: field.to_string(),
+            value_json: serde_json::to_string(value)?,
+            ttl: Some(ttl_to_spec(ttl)),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_hash_set(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                Ok((response.created, response.version))
+            }
+            Err(status) => Err(self.handle_status("forward_hash_set", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_hash_delete(
+        &self,
+        namespace: &str,
+        key: &str,
+        field: &str,
+        target_addr: &str,
+    ) -> Result<bool, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(HashFieldRequest {
+            key: key.to_string(),
+            field: field.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_hash_delete(request).await {
+            Ok(response) => Ok(response.into_inner().deleted),
+            Err(status) => Err(self.handle_status("forward_hash_delete", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_len(
+        &self,
+        namespace: &str,
+        key: &str,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(GetRequest {
+            key: key.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_len(request).await {
+            Ok(response) => Ok(response.into_inner().len),
+            Err(status) => Err(self.handle_status("forward_len", target_addr, status)),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn forward_list_push(
+        &self,
+        namespace: &str,
+        key: &str,
+        values: &[Value],
+        end: ListEnd,
+        ttl: EntryTtl,
+        target_addr: &str,
+    ) -> Result<(u64, u64), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(ListPushRequest {
+            key: key.to_string(),
+            values_json: values.iter().map(serde_json::to_string).collect::<Result<_, _>>()?,
+            front: end == ListEnd::Front,
+            ttl: Some(ttl_to_spec(ttl)),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_list_push(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                Ok((response.len, response.version))
             }
+            Err(status) => Err(self.handle_status("forward_list_push", target_addr, status)),
         }
     }
-}
\ No newline at end of file
+
+    pub async fn forward_list_pop(
+        &self,
+        namespace: &str,
+        key: &str,
+        end: ListEnd,
+        count: usize,
+        target_addr: &str,
+    ) -> Result<Vec<Value>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(ListPopRequest {
+            key: key.to_string(),
+            front: end == ListEnd::Front,
+            count: count as u64,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_list_pop(request).await {
+            Ok(response) => parse_values(response.into_inner().values_json),
+            Err(status) => Err(self.handle_status("forward_list_pop", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_list_range(
+        &self,
+        namespace: &str,
+        key: &str,
+        start: i64,
+        stop: i64,
+        target_addr: &str,
+    ) -> Result<Vec<Value>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(RangeRequest {
+            key: key.to_string(),
+            start,
+            stop,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_list_range(request).await {
+            Ok(response) => parse_values(response.into_inner().values_json),
+            Err(status) => Err(self.handle_status("forward_list_range", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_list_trim(
+        &self,
+        namespace: &str,
+        key: &str,
+        start: i64,
+        stop: i64,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(RangeRequest {
+            key: key.to_string(),
+            start,
+            stop,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_list_trim(request).await {
+            Ok(response) => Ok(response.into_inner().len),
+            Err(status) => Err(self.handle_status("forward_list_trim", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_set_add(
+        &self,
+        namespace: &str,
+        key: &str,
+        members: Vec<String>,
+        ttl: EntryTtl,
+        target_addr: &str,
+    ) -> Result<(u64, u64), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(MembersRequest {
+            key: key.to_string(),
+            members,
+            ttl: Some(ttl_to_spec(ttl)),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_set_add(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                Ok((response.count, response.version))
+            }
+            Err(status) => Err(self.handle_status("forward_set_add", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_set_remove(
+        &self,
+        namespace: &str,
+        key: &str,
+        members: Vec<String>,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(MembersRequest {
+            key: key.to_string(),
+            members,
+            ttl: None,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_set_remove(request).await {
+            Ok(response) => Ok(response.into_inner().count),
+            Err(status) => Err(self.handle_status("forward_set_remove", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_set_contains(
+        &self,
+        namespace: &str,
+        key: &str,
+        member: &str,
+        target_addr: &str,
+    ) -> Result<bool, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(MemberRequest {
+            key: key.to_string(),
+            member: member.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_set_contains(request).await {
+            Ok(response) => Ok(response.into_inner().found),
+            Err(status) => Err(self.handle_status("forward_set_contains", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_set_members(
+        &self,
+        namespace: &str,
+        key: &str,
+        target_addr: &str,
+    ) -> Result<Vec<String>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(GetRequest {
+            key: key.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_set_members(request).await {
+            Ok(response) => Ok(response.into_inner().members),
+            Err(status) => Err(self.handle_status("forward_set_members", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_zset_add(
+        &self,
+        namespace: &str,
+        key: &str,
+        members: Vec<(String, f64)>,
+        ttl: EntryTtl,
+        target_addr: &str,
+    ) -> Result<(u64, u64), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(ZsetAddRequest {
+            key: key.to_string(),
+            members: members
+                .into_iter()
+                .map(|(member, score)| ScoredMember { member, score })
+                .collect(),
+            ttl: Some(ttl_to_spec(ttl)),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_zset_add(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                Ok((response.count, response.version))
+            }
+            Err(status) => Err(self.handle_status("forward_zset_add", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_zset_remove(
+        &self,
+        namespace: &str,
+        key: &str,
+        members: Vec<String>,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(MembersRequest {
+            key: key.to_string(),
+            members,
+            ttl: None,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_zset_remove(request).await {
+            Ok(response) => Ok(response.into_inner().count),
+            Err(status) => Err(self.handle_status("forward_zset_remove", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_zset_rank(
+        &self,
+        namespace: &str,
+        key: &str,
+        member: &str,
+        rev: bool,
+        target_addr: &str,
+    ) -> Result<Option<(u64, f64)>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(ZsetRankRequest {
+            key: key.to_string(),
+            member: member.to_string(),
+            rev,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_zset_rank(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                Ok(response.found.then_some((response.rank, response.score)))
+            }
+            Err(status) => Err(self.handle_status("forward_zset_rank", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_zset_range(
+        &self,
+        namespace: &str,
+        key: &str,
+        range: ZsetRange,
+        rev: bool,
+        target_addr: &str,
+    ) -> Result<Vec<(String, f64)>, RpcClientError> {
+        use zset_range_request::{Range, RankRange, ScoreRange};
+
+        let mut client = self.get_client(target_addr).await?;
+
+        let range = match range {
+            ZsetRange::Rank { start, stop } => Range::ByRank(RankRange { start, stop }),
+            ZsetRange::Score { min, max } => Range::ByScore(ScoreRange { min, max }),
+        };
+        let request = tonic::Request::new(ZsetRangeRequest {
+            key: key.to_string(),
+            range: Some(range),
+            rev,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_zset_range(request).await {
+            Ok(response) => Ok(response
+                .into_inner()
+                .members
+                .into_iter()
+                .map(|m| (m.member, m.score))
+                .collect()),
+            Err(status) => Err(self.handle_status("forward_zset_range", target_addr, status)),
+        }
+    }
+
+    /// 订阅目标节点上前缀为 `prefix` 的键的变更
+    pub async fn forward_watch(&self, namespace: &str, prefix: &str, target_addr: &str) -> Result<WatchStream, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(WatchRequest {
+            prefix: prefix.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_watch(request).await {
+            Ok(response) => Ok(WatchStream { events: response.into_inner() }),
+            Err(status) => Err(self.handle_status("forward_watch", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_transaction(&self, namespace: &str, ops: Vec<TxnOp>, target_addr: &str) -> Result<Vec<u64>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let ops = ops
+            .into_iter()
+            .map(|op| {
+                let op = match op {
+                    TxnOp::Set { key, value, ttl, tags } => {
+                        txn_op::Op::Set(build_set_request(namespace, key, &value, ttl, tags)?)
+                    }
+                    TxnOp::Delete { key } => txn_op::Op::Delete(DeleteRequest {
+                        key,
+                        namespace: namespace.to_string(),
+                    }),
+                    TxnOp::Compare { key, version } => txn_op::Op::Compare(TxnCompare { key, version }),
+                };
+                Ok(proto_cache::TxnOp { op: Some(op) })
+            })
+            .collect::<Result<Vec<_>, RpcClientError>>()?;
+        let request = tonic::Request::new(TransactionRequest {
+            ops,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_transaction(request).await {
+            Ok(response) => Ok(response.into_inner().results),
+            Err(status) => Err(self.handle_status("forward_transaction", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_lock_acquire(
+        &self,
+        namespace: &str,
+        key: String,
+        holder: String,
+        lease: Duration,
+        target_addr: &str,
+    ) -> Result<u64, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(LockAcquireRequest {
+            key,
+            holder,
+            lease_ms: lease.as_millis() as u64,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_lock_acquire(request).await {
+            Ok(response) => Ok(response.into_inner().token),
+            Err(status) => Err(self.handle_status("forward_lock_acquire", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_lock_renew(&self, namespace: &str, key: &str, token: u64, lease: Duration, target_addr: &str) -> Result<(), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(LockRequest {
+            key: key.to_string(),
+            token,
+            lease_ms: lease.as_millis() as u64,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_lock_renew(request).await {
+            Ok(_) => Ok(()),
+            Err(status) => Err(self.handle_status("forward_lock_renew", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_lock_release(&self, namespace: &str, key: &str, token: u64, target_addr: &str) -> Result<(), RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(LockRequest {
+            key: key.to_string(),
+            token,
+            lease_ms: 0,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_lock_release(request).await {
+            Ok(_) => Ok(()),
+            Err(status) => Err(self.handle_status("forward_lock_release", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_lock_info(&self, namespace: &str, key: &str, target_addr: &str) -> Result<Option<(LockState, Duration)>, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(GetRequest {
+            key: key.to_string(),
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_lock_info(request).await {
+            Ok(response) => {
+                let info = response.into_inner();
+                Ok(info.held.then(|| {
+                    let lock = LockState { holder: info.holder, token: info.token };
+                    (lock, Duration::from_millis(info.remaining_ms))
+                }))
+            }
+            Err(status) => Err(self.handle_status("forward_lock_info", target_addr, status)),
+        }
+    }
+
+    pub async fn forward_rate_limit(
+        &self,
+        namespace: &str,
+        key: String,
+        limit: RateLimit,
+        target_addr: &str,
+    ) -> Result<RateLimitOutcome, RpcClientError> {
+        let mut client = self.get_client(target_addr).await?;
+
+        let request = tonic::Request::new(RateLimitRequest {
+            key,
+            limit: limit.limit,
+            period_ms: limit.period.as_millis() as u64,
+            cost: limit.cost,
+            namespace: namespace.to_string(),
+        });
+
+        match client.internal_rate_limit(request).await {
+            Ok(response) => {
+                let response = response.into_inner();
+                Ok(RateLimitOutcome {
+                    allowed: response.allowed,
+                    remaining: response.remaining,
+                    retry_after: Duration::from_millis(response.retry_after_ms),
+                })
+            }
+            Err(status) => Err(self.handle_status("forward_rate_limit", target_addr, status)),
+        }
+    }
+}
+
+/// 远端节点推送的变更事件流
+pub struct WatchStream {
+    events: Streaming<WatchEvent>,
+}
+
+impl WatchStream {
+    /// 下一个事件，远端正常结束时返回 `None`
+    pub async fn next(&mut self) -> Result<Option<WatchItem>, RpcClientError> {
+        let Some(event) = self.events.message().await? else {
+            return Ok(None);
+        };
+        if event.kind == "lagged" {
+            return Ok(Some(WatchItem::Lagged(event.missed)));
+        }
+        let kind = KeyEventKind::parse(&event.kind)
+            .ok_or_else(|| Status::internal(format!("Unknown watch event kind '{}'", event.kind)))?;
+        Ok(Some(WatchItem::Event(KeyEvent {
+            kind,
+            key: event.key,
+            version: event.version,
+            at_ms: event.at_ms,
+        })))
+    }
+}
+
+fn parse_values(values_json: Vec<String>) -> Result<Vec<Value>, RpcClientError> {
+    values_json
+        .iter()
+        .map(|value_json| Ok(serde_json::from_str(value_json)?))
+        .collect()
+}
+
+fn ttl_mode_from_proto(mode: ProtoTtlMode) -> TtlMode {
+    match mode {
+        ProtoTtlMode::Fixed => TtlMode::Fixed,
+        ProtoTtlMode::Idle => TtlMode::Idle,
+        ProtoTtlMode::Permanent => TtlMode::Permanent,
+    }
+}
+
+fn ttl_to_proto(ttl: CacheItemTTL) -> TtlOption {
+    match ttl {
+        CacheItemTTL::Default => TtlOption::UseDefaultTtl(true),
+        CacheItemTTL::Permanent => TtlOption::SetPermanent(true),
+        CacheItemTTL::Custom(d) => TtlOption::SpecificTtlSeconds(d.as_secs()),
+        CacheItemTTL::Idle(d) => TtlOption::IdleTtlSeconds(d.as_secs()),
+    }
+}
+
+fn ttl_to_spec(EntryTtl { ttl, jitter }: EntryTtl) -> TtlSpec {
+    let ttl_option = match ttl {
+        CacheItemTTL::Default => ttl_spec::TtlOption::UseDefaultTtl(true),
+        CacheItemTTL::Permanent => ttl_spec::TtlOption::SetPermanent(true),
+        CacheItemTTL::Custom(d) => ttl_spec::TtlOption::SpecificTtlSeconds(d.as_secs()),
+        CacheItemTTL::Idle(d) => ttl_spec::TtlOption::IdleTtlSeconds(d.as_secs()),
+    };
+    TtlSpec {
+        ttl_option: Some(ttl_option),
+        no_jitter: !jitter,
+    }
+}
+
+fn build_set_request(
+    namespace: &str,
+    key: String,
+    value: &CacheValue,
+    ttl: EntryTtl,
+    tags: Vec<String>,
+) -> Result<SetRequest, RpcClientError> {
+    let (value_json, binary_value) = match value {
+        CacheValue::Binary { content_type, data } => (
+            String::new(),
+            Some(BinaryValue {
+                data: data.to_vec(),
+                content_type: content_type.clone(),
+            }),
+        ),
+        value => (serde_json::to_string(&value.to_json())?, None),
+    };
+    Ok(SetRequest {
+        key,
+        value_json,
+        ttl_option: Some(ttl_to_proto(ttl.ttl)),
+        namespace: namespace.to_string(),
+        tags,
+        binary_value,
+        no_jitter: !ttl.jitter,
+        data_type: value.data_type().unwrap_or_default().to_string(),
+    })
+}
@@ -1,10 +1,20 @@
 // src/rpc_server.rs
 
-use crate::cache::{CacheItemTTL, SharedCache};
+use crate::cache::{
+    CacheItemTTL, CacheValue, EntryTtl, Freshness, JsonPatch, ListEnd, RateLimit, RateLimitOutcome, SharedCache, TtlMode,
+    TxnOp, WatchItem, ZsetRange, forward_events,
+};
+use crate::cluster::SharedCluster;
 use crate::config::SharedSettings;
-use serde_json::Value;
+use crate::error::{CacheError, RpcClientError};
+use crate::namespace::SharedNamespaces;
+use crate::rpc_client::RpcClient;
+use serde_json::{Number, Value};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status, transport::Server};
 use log::{error, info};
 
@@ -13,45 +23,56 @@ pub mod proto_cache {
 }
 
 use proto_cache::{
-    DeleteRequest, DeleteResponse, GetRequest, GetResponse, SetRequest, SetResponse,
+    BinaryValue, CompareAndSetRequest, CompareAndSetResponse, DeleteRequest, DeleteResponse, GetRequest,
+    GetResponse, IncrementRequest, IncrementResponse, InvalidateTagRequest, ListKeysRequest,
+    ListKeysResponse, PatchRequest, PatchResponse, SetRequest,
+    SetResponse, TtlSpec, Freshness as ProtoFreshness, GetMetaResponse, TtlMode as ProtoTtlMode,
+    ExpireRequest, ExpireResponse, GetTtlResponse, HashFieldRequest, HashGetResponse,
+    HashGetAllResponse, HashSetRequest, HashSetResponse, HashDeleteResponse, LenResponse,
+    ListPushRequest, ListPushResponse, ListPopRequest, RangeRequest, ValuesResponse, MembersRequest,
+    MemberRequest, CountResponse, ContainsResponse, MembersResponse, ScoredMember, ZsetAddRequest,
+    ZsetRankRequest, ZsetRankResponse, ZsetRangeRequest, ScoredMembersResponse, WatchRequest, WatchEvent,
+    TransactionRequest, TransactionResponse, txn_op, LockAcquireRequest, LockRequest, LockResponse,
+    LockInfoResponse, RateLimitRequest, RateLimitResponse,
     cache_service_server::{CacheService, CacheServiceServer},
 };
 
+// 每个 watch 流在发送端缓存的事件数
+const WATCH_BUFFER: usize = 256;
+
 pub struct MyCacheService {
-    cache: SharedCache,
+    namespaces: SharedNamespaces,
+    // 供面向客户端的接口把请求转发给键的所有者
+    cluster: SharedCluster,
+    rpc_client: RpcClient,
+}
+
+impl MyCacheService {
+    // gRPC 接口的错误类型就是 tonic::Status，装箱反而要在每个调用处拆开
+    #[allow(clippy::result_large_err)]
+    fn cache(&self, namespace: &str) -> Result<&SharedCache, Status> {
+        self.namespaces.get(namespace).map_err(|e| e.to_status())
+    }
 }
 
 #[tonic::async_trait]
 impl CacheService for MyCacheService {
+    type InternalWatchStream = ReceiverStream<Result<WatchEvent, Status>>;
+
     async fn internal_set(
         &self,
         request: Request<SetRequest>,
     ) -> Result<Response<SetResponse>, Status> {
         let req = request.into_inner();
-        let key = req.key;
-        let value_json = req.value_json;
-        let value: Value = match serde_json::from_str(&value_json) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Failed to parse JSON value for key {}: {}", key, e);
-                return Err(Status::invalid_argument("Invalid JSON value provided"));
-            }
-        };
+        let cache = self.cache(&req.namespace)?;
+        let (key, value, ttl, tags) = parse_set_request(req)?;
 
-        let ttl = match req.ttl_option {
-            Some(proto_cache::set_request::TtlOption::UseDefaultTtl(true)) => CacheItemTTL::Default,
-            Some(proto_cache::set_request::TtlOption::SetPermanent(true)) => {
-                CacheItemTTL::Permanent
-            }
-            Some(proto_cache::set_request::TtlOption::SpecificTtlSeconds(sec)) => {
-                CacheItemTTL::Custom(Duration::from_secs(sec))
-            }
-            _ => CacheItemTTL::Default,
-        };
-
-        self.cache.set(key, value, ttl).await;
+        let version = cache
+            .set(key, value, ttl, tags)
+            .await
+            .map_err(|e| e.to_status())?;
 
-        Ok(Response::new(SetResponse {}))
+        Ok(Response::new(SetResponse { version }))
     }
 
     async fn internal_get(
@@ -59,16 +80,35 @@ impl CacheService for MyCacheService {
         request: Request<GetRequest>,
     ) -> Result<Response<GetResponse>, Status> {
         let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
         let key = req.key;
 
-        match self.cache.get(&key).await {
-            Some(value) => {
-                let value_json =
-                    serde_json::to_string(&value).unwrap_or_else(|_| "null".to_string());
-                Ok(Response::new(GetResponse { value_json }))
+        let Some((value, version, freshness)) =
+            cache.get_or_load(&key).await.map_err(|e| e.to_status())?
+        else {
+            return Err(Status::not_found(format!("Key '{}' not found", key)));
+        };
+        let freshness = match freshness {
+            Freshness::Fresh => ProtoFreshness::Fresh,
+            Freshness::Stale => ProtoFreshness::Stale,
+            Freshness::StaleIfError => ProtoFreshness::StaleIfError,
+        } as i32;
+
+        match value {
+            CacheValue::Binary { content_type, data } => {
+                Ok(Response::new(GetResponse {
+                    value_json: String::new(),
+                    version,
+                    binary_value: Some(BinaryValue { data: data.to_vec(), content_type }),
+                    freshness,
+                    data_type: String::new(),
+                }))
             }
-            None => {
-                Err(Status::not_found(format!("Key '{}' not found", key)))
+            value => {
+                let value_json = serde_json::to_string(&value.to_json())
+                    .unwrap_or_else(|_| "null".to_string());
+                let data_type = value.data_type().unwrap_or_default().to_string();
+                Ok(Response::new(GetResponse { value_json, version, binary_value: None, freshness, data_type }))
             }
         }
     }
@@ -78,21 +118,686 @@ impl CacheService for MyCacheService {
         request: Request<DeleteRequest>,
     ) -> Result<Response<DeleteResponse>, Status> {
         let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
         let key = req.key;
 
-        let deleted_count = self.cache.delete(&key).await;
+        let deleted_count = cache.delete(&key).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(DeleteResponse { deleted_count }))
+    }
+
+    async fn internal_compare_and_set(
+        &self,
+        request: Request<CompareAndSetRequest>,
+    ) -> Result<Response<CompareAndSetResponse>, Status> {
+        let req = request.into_inner();
+        let set = req
+            .set
+            .ok_or_else(|| Status::invalid_argument("Missing set request"))?;
+        let cache = self.cache(&set.namespace)?;
+        let (key, value, ttl, tags) = parse_set_request(set)?;
+
+        let version = cache
+            .compare_and_set(key, value, ttl, tags, req.expected_version)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(CompareAndSetResponse { version }))
+    }
+
+    async fn internal_increment(
+        &self,
+        request: Request<IncrementRequest>,
+    ) -> Result<Response<IncrementResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let delta: Number = serde_json::from_str(&req.delta_json)
+            .map_err(|_| Status::invalid_argument("Invalid delta provided"))?;
+        let initial: Number = serde_json::from_str(&req.initial_json)
+            .map_err(|_| Status::invalid_argument("Invalid initial value provided"))?;
+        let ttl = ttl_from_spec(req.ttl);
+
+        let (value, version) = cache
+            .increment(req.key, delta, initial, ttl)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        let value_json = serde_json::to_string(&value).unwrap_or_else(|_| "null".to_string());
+        Ok(Response::new(IncrementResponse { value_json, version }))
+    }
+
+    async fn internal_patch(
+        &self,
+        request: Request<PatchRequest>,
+    ) -> Result<Response<PatchResponse>, Status> {
+        use proto_cache::patch_request::Patch;
+
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let patch = match req.patch {
+            Some(Patch::MergePatchJson(doc)) => serde_json::from_str(&doc).map(JsonPatch::Merge),
+            Some(Patch::JsonPatchJson(ops)) => serde_json::from_str(&ops).map(JsonPatch::Operations),
+            None => return Err(Status::invalid_argument("Missing patch document")),
+        }
+        .map_err(|_| Status::invalid_argument("Invalid patch document provided"))?;
+
+        let (value, version) = cache
+            .patch(&req.key, &patch)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        let value_json = serde_json::to_string(&value).unwrap_or_else(|_| "null".to_string());
+        Ok(Response::new(PatchResponse { value_json, version }))
+    }
+
+    async fn internal_invalidate_tag(
+        &self,
+        request: Request<InvalidateTagRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let deleted_count = cache.invalidate_tag(&req.tag).await;
+        info!("Invalidated {} entries tagged '{}'", deleted_count, req.tag);
 
         Ok(Response::new(DeleteResponse { deleted_count }))
     }
+
+    async fn internal_list_keys(
+        &self,
+        request: Request<ListKeysRequest>,
+    ) -> Result<Response<ListKeysResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let cursor = (!req.cursor.is_empty()).then_some(req.cursor.as_str());
+
+        let keys = cache.keys(&req.prefix, cursor, req.limit as usize);
+
+        Ok(Response::new(ListKeysResponse { keys }))
+    }
+
+    async fn internal_get_meta(
+        &self,
+        request: Request<GetRequest>,
+    ) -> Result<Response<GetMetaResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let Some(meta) = cache.meta(&req.key).await else {
+            return Err(CacheError::KeyNotFound.to_status());
+        };
+        let ttl_mode = ttl_mode_to_proto(meta.ttl_mode);
+
+        Ok(Response::new(GetMetaResponse {
+            created_at_ms: meta.created_at_ms,
+            updated_at_ms: meta.updated_at_ms,
+            last_access_ms: meta.last_access_ms.unwrap_or(0),
+            access_count: meta.access_count,
+            ttl_mode,
+            remaining_ttl_ms: meta.remaining_ttl_ms.unwrap_or(0),
+            stale: meta.stale,
+            size: meta.size,
+            version: meta.version,
+            tags: meta.tags,
+            expires_at_ms: meta.expires_at_ms.unwrap_or(0),
+        }))
+    }
+
+    async fn internal_expire(
+        &self,
+        request: Request<ExpireRequest>,
+    ) -> Result<Response<ExpireResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let ttl = ttl_from_spec(req.ttl);
+
+        let version = cache.expire(&req.key, ttl.ttl).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(ExpireResponse { version }))
+    }
+
+    async fn internal_get_ttl(
+        &self,
+        request: Request<GetRequest>,
+    ) -> Result<Response<GetTtlResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let Some((ttl_mode, remaining)) = cache.ttl(&req.key).await else {
+            return Err(CacheError::KeyNotFound.to_status());
+        };
+
+        Ok(Response::new(GetTtlResponse {
+            ttl_mode: ttl_mode_to_proto(ttl_mode),
+            remaining_ttl_ms: remaining.map_or(0, |ttl| ttl.as_millis() as u64),
+        }))
+    }
+
+    async fn internal_hash_get(
+        &self,
+        request: Request<HashFieldRequest>,
+    ) -> Result<Response<HashGetResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let value = cache.hash_get(&req.key, &req.field).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(HashGetResponse {
+            found: value.is_some(),
+            value_json: value.map(|v| v.to_string()).unwrap_or_default(),
+        }))
+    }
+
+    async fn internal_hash_get_all(
+        &self,
+        request: Request<GetRequest>,
+    ) -> Result<Response<HashGetAllResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let Some(fields) = cache.hash_get_all(&req.key).await.map_err(|e| e.to_status())? else {
+            return Err(CacheError::KeyNotFound.to_status());
+        };
+
+        Ok(Response::new(HashGetAllResponse {
+            fields: fields.into_iter().map(|(field, value)| (field, value.to_string())).collect(),
+        }))
+    }
+
+    async fn internal_hash_set(
+        &self,
+        request: Request<HashSetRequest>,
+    ) -> Result<Response<HashSetResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let value: Value = serde_json::from_str(&req.value_json)
+            .map_err(|_| Status::invalid_argument("Invalid JSON value provided"))?;
+        let ttl = ttl_from_spec(req.ttl);
+
+        let (created, version) = cache
+            .hash_set(req.key, req.field, value, ttl)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(HashSetResponse { created, version }))
+    }
+
+    async fn internal_hash_delete(
+        &self,
+        request: Request<HashFieldRequest>,
+    ) -> Result<Response<HashDeleteResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let deleted = cache.hash_delete(&req.key, &req.field).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(HashDeleteResponse { deleted }))
+    }
+
+    async fn internal_len(
+        &self,
+        request: Request<GetRequest>,
+    ) -> Result<Response<LenResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let len = cache.len(&req.key).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(LenResponse { len }))
+    }
+
+    async fn internal_list_push(
+        &self,
+        request: Request<ListPushRequest>,
+    ) -> Result<Response<ListPushResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let values = parse_values(&req.values_json)?;
+        let end = if req.front { ListEnd::Front } else { ListEnd::Back };
+        let ttl = ttl_from_spec(req.ttl);
+
+        let (len, version) = cache
+            .list_push(req.key, values, end, ttl)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(ListPushResponse { len, version }))
+    }
+
+    async fn internal_list_pop(
+        &self,
+        request: Request<ListPopRequest>,
+    ) -> Result<Response<ValuesResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let end = if req.front { ListEnd::Front } else { ListEnd::Back };
+
+        let values = cache
+            .list_pop(&req.key, end, req.count as usize)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(ValuesResponse { values_json: values.iter().map(Value::to_string).collect() }))
+    }
+
+    async fn internal_list_range(
+        &self,
+        request: Request<RangeRequest>,
+    ) -> Result<Response<ValuesResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let values = cache
+            .list_range(&req.key, req.start, req.stop)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(ValuesResponse { values_json: values.iter().map(Value::to_string).collect() }))
+    }
+
+    async fn internal_list_trim(
+        &self,
+        request: Request<RangeRequest>,
+    ) -> Result<Response<LenResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let len = cache
+            .list_trim(&req.key, req.start, req.stop)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(LenResponse { len }))
+    }
+
+    async fn internal_set_add(
+        &self,
+        request: Request<MembersRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let ttl = ttl_from_spec(req.ttl);
+
+        let (count, version) = cache
+            .set_add(req.key, req.members, ttl)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(CountResponse { count, version }))
+    }
+
+    async fn internal_set_remove(
+        &self,
+        request: Request<MembersRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let count = cache.set_remove(&req.key, &req.members).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(CountResponse { count, version: 0 }))
+    }
+
+    async fn internal_set_contains(
+        &self,
+        request: Request<MemberRequest>,
+    ) -> Result<Response<ContainsResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let found = cache.set_contains(&req.key, &req.member).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(ContainsResponse { found }))
+    }
+
+    async fn internal_set_members(
+        &self,
+        request: Request<GetRequest>,
+    ) -> Result<Response<MembersResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let members = cache.set_members(&req.key).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(MembersResponse { members }))
+    }
+
+    async fn internal_zset_add(
+        &self,
+        request: Request<ZsetAddRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        // NaN 和无穷大无法参与排序与按分数的范围查询
+        if req.members.iter().any(|m| !m.score.is_finite()) {
+            return Err(Status::invalid_argument("Score must be a finite number"));
+        }
+        let members = req.members.into_iter().map(|m| (m.member, m.score)).collect();
+        let ttl = ttl_from_spec(req.ttl);
+
+        let (count, version) = cache
+            .zset_add(req.key, members, ttl)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(CountResponse { count, version }))
+    }
+
+    async fn internal_zset_remove(
+        &self,
+        request: Request<MembersRequest>,
+    ) -> Result<Response<CountResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let count = cache.zset_remove(&req.key, &req.members).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(CountResponse { count, version: 0 }))
+    }
+
+    async fn internal_zset_rank(
+        &self,
+        request: Request<ZsetRankRequest>,
+    ) -> Result<Response<ZsetRankResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let rank = cache
+            .zset_rank(&req.key, &req.member, req.rev)
+            .await
+            .map_err(|e| e.to_status())?;
+
+        let response = match rank {
+            Some((rank, score)) => ZsetRankResponse { found: true, rank, score },
+            None => ZsetRankResponse::default(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn internal_zset_range(
+        &self,
+        request: Request<ZsetRangeRequest>,
+    ) -> Result<Response<ScoredMembersResponse>, Status> {
+        use proto_cache::zset_range_request::Range;
+
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let range = match req.range {
+            Some(Range::ByRank(range)) => ZsetRange::Rank { start: range.start, stop: range.stop },
+            Some(Range::ByScore(range)) => ZsetRange::Score { min: range.min, max: range.max },
+            None => return Err(Status::invalid_argument("Range is required")),
+        };
+
+        let members = cache.zset_range(&req.key, range, req.rev).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(ScoredMembersResponse {
+            members: members
+                .into_iter()
+                .map(|(member, score)| ScoredMember { member, score })
+                .collect(),
+        }))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn internal_watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::InternalWatchStream>, Status> {
+        let req = request.into_inner();
+        let cache = Arc::clone(self.cache(&req.namespace)?);
+        // 先订阅再返回，调用方拿到流之后的变更都不会漏掉
+        let events = cache.subscribe();
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+        tokio::spawn(async move {
+            forward_events(events, &req.prefix, tx, |item| Ok(watch_event_to_proto(item))).await;
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn internal_transaction(
+        &self,
+        request: Request<TransactionRequest>,
+    ) -> Result<Response<TransactionResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let ops = req
+            .ops
+            .into_iter()
+            .map(|op| match op.op {
+                Some(txn_op::Op::Set(set)) => {
+                    let (key, value, ttl, tags) = parse_set_request(set)?;
+                    Ok(TxnOp::Set { key, value, ttl, tags })
+                }
+                Some(txn_op::Op::Delete(delete)) => Ok(TxnOp::Delete { key: delete.key }),
+                Some(txn_op::Op::Compare(compare)) => Ok(TxnOp::Compare {
+                    key: compare.key,
+                    version: compare.version,
+                }),
+                None => Err(Status::invalid_argument("Missing transaction operation")),
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+
+        let results = cache.transaction(ops).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(TransactionResponse { results }))
+    }
+
+    async fn internal_lock_acquire(
+        &self,
+        request: Request<LockAcquireRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let token = cache
+            .lock_acquire(req.key, req.holder, Duration::from_millis(req.lease_ms))
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(LockResponse { token }))
+    }
+
+    async fn internal_lock_renew(
+        &self,
+        request: Request<LockRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        cache
+            .lock_renew(&req.key, req.token, Duration::from_millis(req.lease_ms))
+            .await
+            .map_err(|e| e.to_status())?;
+
+        Ok(Response::new(LockResponse { token: req.token }))
+    }
+
+    async fn internal_lock_release(
+        &self,
+        request: Request<LockRequest>,
+    ) -> Result<Response<LockResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        cache.lock_release(&req.key, req.token).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(LockResponse { token: req.token }))
+    }
+
+    async fn internal_lock_info(
+        &self,
+        request: Request<GetRequest>,
+    ) -> Result<Response<LockInfoResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+
+        let response = match cache.lock_info(&req.key).await.map_err(|e| e.to_status())? {
+            Some((lock, remaining)) => LockInfoResponse {
+                held: true,
+                holder: lock.holder,
+                token: lock.token,
+                remaining_ms: remaining.as_millis() as u64,
+            },
+            None => LockInfoResponse::default(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn rate_limit(
+        &self,
+        request: Request<RateLimitRequest>,
+    ) -> Result<Response<RateLimitResponse>, Status> {
+        let req = request.into_inner();
+        let limit = parse_rate_limit(&req)?;
+        let target_addr = self.cluster.get_node_for_key(&req.key);
+
+        let outcome = if target_addr == self.cluster.my_addr {
+            let cache = self.cache(&req.namespace)?;
+            cache.rate_limit(req.key, limit).await.map_err(|e| e.to_status())?
+        } else {
+            info!("Forwarding RATELIMIT for key '{}' to {}", req.key, target_addr);
+            self.rpc_client
+                .forward_rate_limit(&req.namespace, req.key, limit, &target_addr)
+                .await
+                .map_err(rpc_error_to_status)?
+        };
+
+        Ok(Response::new(rate_limit_response(outcome)))
+    }
+
+    async fn internal_rate_limit(
+        &self,
+        request: Request<RateLimitRequest>,
+    ) -> Result<Response<RateLimitResponse>, Status> {
+        let req = request.into_inner();
+        let cache = self.cache(&req.namespace)?;
+        let limit = parse_rate_limit(&req)?;
+
+        let outcome = cache.rate_limit(req.key, limit).await.map_err(|e| e.to_status())?;
+
+        Ok(Response::new(rate_limit_response(outcome)))
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_rate_limit(req: &RateLimitRequest) -> Result<RateLimit, Status> {
+    RateLimit::new(req.limit, Duration::from_millis(req.period_ms), req.cost).map_err(Status::invalid_argument)
+}
+
+fn rate_limit_response(outcome: RateLimitOutcome) -> RateLimitResponse {
+    RateLimitResponse {
+        allowed: outcome.allowed,
+        remaining: outcome.remaining,
+        retry_after_ms: outcome.retry_after.as_millis() as u64,
+    }
+}
+
+/// 转发失败时，所有者返回的状态原样交给调用方，连接错误视为不可用
+fn rpc_error_to_status(err: RpcClientError) -> Status {
+    match err {
+        RpcClientError::Status(status) => *status,
+        err => Status::unavailable(err.to_string()),
+    }
+}
+
+fn watch_event_to_proto(item: WatchItem) -> WatchEvent {
+    match item {
+        WatchItem::Event(event) => WatchEvent {
+            kind: event.kind.as_str().to_string(),
+            key: event.key,
+            version: event.version,
+            at_ms: event.at_ms,
+            missed: 0,
+        },
+        WatchItem::Lagged(missed) => WatchEvent {
+            kind: "lagged".to_string(),
+            missed,
+            ..Default::default()
+        },
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_values(values_json: &[String]) -> Result<Vec<Value>, Status> {
+    values_json
+        .iter()
+        .map(|value_json| serde_json::from_str(value_json))
+        .collect::<Result<_, _>>()
+        .map_err(|_| Status::invalid_argument("Invalid JSON value provided"))
+}
+
+fn ttl_mode_to_proto(mode: TtlMode) -> i32 {
+    let mode = match mode {
+        TtlMode::Fixed => ProtoTtlMode::Fixed,
+        TtlMode::Idle => ProtoTtlMode::Idle,
+        TtlMode::Permanent => ProtoTtlMode::Permanent,
+    };
+    mode as i32
+}
+
+fn ttl_from_spec(spec: Option<TtlSpec>) -> EntryTtl {
+    use proto_cache::ttl_spec::TtlOption;
+
+    let Some(spec) = spec else {
+        return CacheItemTTL::Default.into();
+    };
+    let ttl = match spec.ttl_option {
+        Some(TtlOption::UseDefaultTtl(true)) => CacheItemTTL::Default,
+        Some(TtlOption::SetPermanent(true)) => CacheItemTTL::Permanent,
+        Some(TtlOption::SpecificTtlSeconds(sec)) => CacheItemTTL::Custom(Duration::from_secs(sec)),
+        Some(TtlOption::IdleTtlSeconds(sec)) => CacheItemTTL::Idle(Duration::from_secs(sec)),
+        _ => CacheItemTTL::Default,
+    };
+    EntryTtl { ttl, jitter: !spec.no_jitter }
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_set_request(req: SetRequest) -> Result<(String, CacheValue, EntryTtl, Vec<String>), Status> {
+    let key = req.key;
+    let value = match req.binary_value {
+        Some(binary) => CacheValue::Binary {
+            content_type: binary.content_type,
+            data: binary.data.into(),
+        },
+        None => match serde_json::from_str::<Value>(&req.value_json) {
+            Ok(v) => CacheValue::from_json(v, &req.data_type)
+                .ok_or_else(|| Status::invalid_argument("Invalid data type"))?,
+            Err(e) => {
+                error!("Failed to parse JSON value for key {}: {}", key, e);
+                return Err(Status::invalid_argument("Invalid JSON value provided"));
+            }
+        },
+    };
+
+    let ttl = match req.ttl_option {
+        Some(proto_cache::set_request::TtlOption::UseDefaultTtl(true)) => CacheItemTTL::Default,
+        Some(proto_cache::set_request::TtlOption::SetPermanent(true)) => {
+            CacheItemTTL::Permanent
+        }
+        Some(proto_cache::set_request::TtlOption::SpecificTtlSeconds(sec)) => {
+            CacheItemTTL::Custom(Duration::from_secs(sec))
+        }
+        Some(proto_cache::set_request::TtlOption::IdleTtlSeconds(sec)) => {
+            CacheItemTTL::Idle(Duration::from_secs(sec))
+        }
+        _ => CacheItemTTL::Default,
+    };
+    let ttl = EntryTtl { ttl, jitter: !req.no_jitter };
+
+    Ok((key, value, ttl, req.tags))
 }
 
 pub async fn run_rpc_server(
     settings: SharedSettings,
-    cache: SharedCache,
+    namespaces: SharedNamespaces,
+    cluster: SharedCluster,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let addr: SocketAddr = settings.rpc_addr.parse()?;
 
-    let service = MyCacheService { cache };
+    let rpc_client = RpcClient::new(3000); // 3 秒连接超时
+    let service = MyCacheService { namespaces, cluster, rpc_client };
 
     info!("gRPC server (Internal) listening on {}", addr);
 
@@ -103,3 +808,70 @@ pub async fn run_rpc_server(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::Cluster;
+    use crate::config::{CacheSettings, Settings};
+    use crate::namespace::Namespaces;
+    use proto_cache::TxnOp as ProtoTxnOp;
+
+    fn test_service() -> MyCacheService {
+        let settings = Settings::for_tests(CacheSettings::for_tests());
+        MyCacheService {
+            namespaces: Arc::new(Namespaces::new(&settings)),
+            cluster: Arc::new(Cluster::new(&settings)),
+            rpc_client: RpcClient::new(100),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_rejects_lock_values() {
+        let service = test_service();
+        let set = SetRequest {
+            key: "job".to_string(),
+            value_json: r#"{"holder":"a","token":1}"#.to_string(),
+            data_type: "lock".to_string(),
+            ..Default::default()
+        };
+        assert_rejected(&service, set).await;
+
+        let cache = service.cache("").unwrap();
+        assert_eq!(cache.lock_info("job").await.unwrap(), None);
+        assert!(cache.get_versioned("job").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_rejects_bucket_values() {
+        let service = test_service();
+        let set = SetRequest {
+            key: "api".to_string(),
+            value_json: r#"{"tokens":100.0,"updated_ms":0}"#.to_string(),
+            data_type: "bucket".to_string(),
+            ..Default::default()
+        };
+        assert_rejected(&service, set).await;
+
+        // 桶没有混进普通存储，限流仍从满的桶开始
+        let cache = service.cache("").unwrap();
+        assert!(cache.get_versioned("api").await.is_none());
+        let limit = RateLimit::new(2, Duration::from_secs(10), 1).unwrap();
+        assert_eq!(cache.rate_limit("api".to_string(), limit).await.unwrap().remaining, 1);
+    }
+
+    /// 锁和令牌桶只能通过各自的接口写入，不能经由普通写入、CAS 或事务伪造
+    async fn assert_rejected(service: &MyCacheService, set: SetRequest) {
+        let status = service.internal_set(Request::new(set.clone())).await.unwrap_err();
+        assert!(matches!(CacheError::from_status(&status), Some(CacheError::WrongType)));
+        let cas = CompareAndSetRequest { set: Some(set.clone()), expected_version: 0 };
+        let status = service.internal_compare_and_set(Request::new(cas)).await.unwrap_err();
+        assert!(matches!(CacheError::from_status(&status), Some(CacheError::WrongType)));
+        let txn = TransactionRequest {
+            ops: vec![ProtoTxnOp { op: Some(txn_op::Op::Set(set)) }],
+            namespace: String::new(),
+        };
+        let status = service.internal_transaction(Request::new(txn)).await.unwrap_err();
+        assert!(matches!(CacheError::from_status(&status), Some(CacheError::WrongType)));
+    }
+}
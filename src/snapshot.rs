@@ -0,0 +1,293 @@
+// src/snapshot.rs
+
+use crate::cache::EntrySnapshot;
+use crate::config::{SharedSettings, SnapshotSettings};
+use crate::namespace::{Namespaces, SharedNamespaces};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+const FILE_PREFIX: &str = "snapshot-";
+const FILE_SUFFIX: &str = ".json";
+
+// 同一时间只允许一个快照写入，避免定期快照与手动快照互相覆盖
+static SNAPSHOT_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// 一个节点在某一时刻的全部条目
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    // 快照时间（Unix 毫秒），恢复时据此扣除停机期间流逝的 TTL
+    created_at_ms: u64,
+    // 命名空间 -> 条目
+    namespaces: HashMap<String, Vec<EntrySnapshot>>,
+}
+
+/// 快照的结果
+#[derive(Debug, Serialize)]
+pub struct SnapshotInfo {
+    pub path: PathBuf,
+    pub entries: usize,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 在没有快照写入的情况下执行 `f`。会阻塞当前线程，只能在运行时之外的线程中调用
+pub fn while_no_snapshot<T>(f: impl FnOnce() -> T) -> T {
+    let _guard = SNAPSHOT_LOCK.blocking_lock();
+    f()
+}
+
+/// 将本节点所有命名空间的条目写入新的快照文件，并清理超出保留数量的旧快照。
+/// 先写临时文件再重命名，中途失败不会留下不完整的快照。
+pub async fn take_snapshot(
+    settings: &SnapshotSettings,
+    namespaces: &Namespaces,
+) -> io::Result<SnapshotInfo> {
+    let _guard = SNAPSHOT_LOCK.lock().await;
+
+    let snapshot = Snapshot {
+        created_at_ms: now_ms(),
+        namespaces: namespaces
+            .iter()
+            .map(|(name, cache)| (name.clone(), cache.export()))
+            .collect(),
+    };
+    let entries = snapshot.namespaces.values().map(Vec::len).sum();
+
+    let settings = settings.clone();
+    let path = tokio::task::spawn_blocking(move || -> io::Result<PathBuf> {
+        let dir = Path::new(&settings.dir);
+        fs::create_dir_all(dir)?;
+
+        let name = format!("{}{}{}", FILE_PREFIX, snapshot.created_at_ms, FILE_SUFFIX);
+        let path = dir.join(name);
+        let tmp_path = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, &snapshot)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+
+        remove_old_snapshots(dir, settings.retain)?;
+        Ok(path)
+    })
+    .await??;
+
+    info!("Snapshot of {} entries written to {}", entries, path.display());
+    Ok(SnapshotInfo { path, entries })
+}
+
+/// 加载目录中最新的快照，没有快照时返回 `None`。
+/// 配置中不存在的命名空间会被跳过。
+pub async fn restore_latest(
+    settings: &SnapshotSettings,
+    namespaces: &Namespaces,
+) -> io::Result<Option<SnapshotInfo>> {
+    let dir = PathBuf::from(&settings.dir);
+    let loaded = tokio::task::spawn_blocking(move || -> io::Result<Option<(PathBuf, Snapshot)>> {
+        let Some((_, path)) = list_snapshots(&dir)?.pop() else {
+            return Ok(None);
+        };
+        let reader = BufReader::new(File::open(&path)?);
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        Ok(Some((path, snapshot)))
+    })
+    .await??;
+
+    let Some((path, snapshot)) = loaded else {
+        return Ok(None);
+    };
+
+    let elapsed = Duration::from_millis(now_ms().saturating_sub(snapshot.created_at_ms));
+    let mut entries = 0;
+    for (name, ns_entries) in snapshot.namespaces {
+        match namespaces.get(&name) {
+            Ok(cache) => entries += cache.import(ns_entries, elapsed).await,
+            Err(e) => warn!("Skipping snapshot entries of namespace '{}': {}", name, e),
+        }
+    }
+
+    info!("Restored {} entries from {}", entries, path.display());
+    Ok(Some(SnapshotInfo { path, entries }))
+}
+
+/// 按配置的间隔定期快照，间隔为 0 时不启动
+pub fn spawn_periodic(settings: SharedSettings, namespaces: SharedNamespaces) {
+    if settings.snapshot.interval_seconds == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(settings.snapshot.interval_seconds);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // 第一次 tick 立即完成，跳过它以免启动时就写一份快照
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = take_snapshot(&settings.snapshot, &namespaces).await {
+                error!("Periodic snapshot failed: {}", e);
+            }
+        }
+    });
+    info!("Spawned periodic snapshot task ({:?}).", interval);
+}
+
+/// 目录中的快照文件，按快照时间升序排列
+fn list_snapshots(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in read_dir {
+        let path = entry?.path();
+        let timestamp = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(FILE_PREFIX))
+            .and_then(|name| name.strip_suffix(FILE_SUFFIX))
+            .and_then(|ts| ts.parse::<u64>().ok());
+        if let Some(timestamp) = timestamp {
+            snapshots.push((timestamp, path));
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// 目录中最新快照的时间（Unix 毫秒），没有快照时为 `None`
+pub fn latest_created_at(dir: &Path) -> io::Result<Option<u64>> {
+    Ok(list_snapshots(dir)?.pop().map(|(timestamp, _)| timestamp))
+}
+
+fn remove_old_snapshots(dir: &Path, retain: usize) -> io::Result<()> {
+    let snapshots = list_snapshots(dir)?;
+    let excess = snapshots.len().saturating_sub(retain.max(1));
+    for (_, path) in snapshots.into_iter().take(excess) {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CacheItemTTL, SnapshotValue};
+    use crate::config::{CacheSettings, Settings};
+    use crate::namespace::DEFAULT_NAMESPACE;
+    use serde_json::{Value, json};
+
+    fn test_settings(name: &str, retain: usize) -> (Settings, SnapshotSettings) {
+        let dir = std::env::temp_dir().join(format!("my-cache-snapshots-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let settings = Settings::for_tests(CacheSettings::for_tests());
+        let snapshot = SnapshotSettings {
+            dir: dir.to_string_lossy().into_owned(),
+            interval_seconds: 0,
+            restore_on_boot: true,
+            retain,
+        };
+        (settings, snapshot)
+    }
+
+    fn write_snapshot(dir: &Path, snapshot: &Snapshot) {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(format!("{}{}{}", FILE_PREFIX, snapshot.created_at_ms, FILE_SUFFIX));
+        fs::write(path, serde_json::to_vec(snapshot).unwrap()).unwrap();
+    }
+
+    fn entry(key: &str, value: Value, ttl_ms: Option<u64>, version: u64) -> EntrySnapshot {
+        EntrySnapshot {
+            key: key.to_string(),
+            value: SnapshotValue { value, content_type: None, data_type: None },
+            ttl_ms,
+            idle_ms: None,
+            tags: vec![],
+            version,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_snapshot_writes_file_and_prunes() {
+        let (settings, snapshot_settings) = test_settings("prune", 2);
+        let namespaces = Namespaces::new(&settings);
+        let cache = namespaces.get(DEFAULT_NAMESPACE).unwrap();
+        cache.set("a".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+
+        let mut paths = Vec::new();
+        for _ in 0..3 {
+            let info = take_snapshot(&snapshot_settings, &namespaces).await.unwrap();
+            assert_eq!(info.entries, 1);
+            assert!(info.path.exists());
+            paths.push(info.path);
+            // 文件名精确到毫秒，避免同一毫秒内的快照互相覆盖
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // 只保留最新的两份
+        let dir = Path::new(&snapshot_settings.dir);
+        let remaining: Vec<PathBuf> = list_snapshots(dir).unwrap().into_iter().map(|(_, path)| path).collect();
+        assert_eq!(remaining, paths[1..]);
+
+        let written: Snapshot = serde_json::from_slice(&fs::read(&paths[2]).unwrap()).unwrap();
+        assert_eq!(written.namespaces[DEFAULT_NAMESPACE].len(), 1);
+        assert_eq!(written.namespaces[DEFAULT_NAMESPACE][0].key, "a");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restore_latest_uses_newest_snapshot_and_ages_ttl() {
+        let (settings, snapshot_settings) = test_settings("restore", 3);
+        let dir = PathBuf::from(&snapshot_settings.dir);
+
+        // 没有快照时什么都不做
+        let namespaces = Namespaces::new(&settings);
+        assert!(restore_latest(&snapshot_settings, &namespaces).await.unwrap().is_none());
+
+        let now = now_ms();
+        let older = Snapshot {
+            created_at_ms: now - 60_000,
+            namespaces: HashMap::from([(DEFAULT_NAMESPACE.to_string(), vec![entry("k", json!("old"), None, 1)])]),
+        };
+        // 30 秒前的快照，条目当时还剩 40 秒，恢复后应只剩约 10 秒；
+        // 当时只剩 20 秒的条目在停机期间已经过期
+        let newer = Snapshot {
+            created_at_ms: now - 30_000,
+            namespaces: HashMap::from([(
+                DEFAULT_NAMESPACE.to_string(),
+                vec![
+                    entry("k", json!("new"), Some(40_000), 2),
+                    entry("gone", json!(1), Some(20_000), 3),
+                ],
+            )]),
+        };
+        write_snapshot(&dir, &older);
+        write_snapshot(&dir, &newer);
+
+        let info = restore_latest(&snapshot_settings, &namespaces).await.unwrap().unwrap();
+        assert!(info.path.ends_with(format!("{}{}{}", FILE_PREFIX, newer.created_at_ms, FILE_SUFFIX)));
+        assert_eq!(info.entries, 1);
+
+        let cache = namespaces.get(DEFAULT_NAMESPACE).unwrap();
+        assert_eq!(cache.get("k").await, Some(json!("new")));
+        assert_eq!(cache.get("gone").await, None);
+        let (_, remaining) = cache.ttl("k").await.unwrap();
+        let remaining = remaining.unwrap();
+        assert!(remaining <= Duration::from_secs(10) && remaining > Duration::from_secs(8), "{:?}", remaining);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+// src/sorted_set.rs
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+
+/// 按 `f64::total_cmp` 排序的分数
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// 有序集合：成员按分数从小到大排序，分数相同时按成员名排序
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedSet {
+    scores: HashMap<String, f64>,
+    order: BTreeSet<(Score, String)>,
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// 添加成员或更新其分数，返回成员是否新加入
+    pub fn insert(&mut self, member: String, score: f64) -> bool {
+        let previous = self.scores.insert(member.clone(), score);
+        if let Some(previous) = previous {
+            self.order.remove(&(Score(previous), member.clone()));
+        }
+        self.order.insert((Score(score), member));
+        previous.is_none()
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.order.remove(&(Score(score), member.to_string())),
+            None => false,
+        }
+    }
+
+    /// 成员的排名（从 0 开始）和分数，`rev` 时按分数从大到小排名
+    pub fn rank(&self, member: &str, rev: bool) -> Option<(usize, f64)> {
+        let score = *self.scores.get(member)?;
+        let below = self.order.range(..(Score(score), member.to_string())).count();
+        let rank = if rev { self.len() - 1 - below } else { below };
+        Some((rank, score))
+    }
+
+    /// 按排名读取一段成员
+    pub fn range_by_rank(&self, range: RangeInclusive<usize>, rev: bool) -> Vec<(String, f64)> {
+        let take = range.end() + 1 - range.start();
+        let entries: Box<dyn Iterator<Item = &(Score, String)>> = if rev {
+            Box::new(self.order.iter().rev())
+        } else {
+            Box::new(self.order.iter())
+        };
+        entries
+            .skip(*range.start())
+            .take(take)
+            .map(|(score, member)| (member.clone(), score.0))
+            .collect()
+    }
+
+    /// 读取分数在 `[min, max]` 之间的成员
+    pub fn range_by_score(&self, min: f64, max: f64, rev: bool) -> Vec<(String, f64)> {
+        if min.total_cmp(&max) == Ordering::Greater {
+            return Vec::new();
+        }
+        let entries = self
+            .order
+            .range((Score(min), String::new())..)
+            .take_while(|(score, _)| score.0.total_cmp(&max) != Ordering::Greater)
+            .map(|(score, member)| (member.clone(), score.0));
+        if rev {
+            let mut entries: Vec<_> = entries.collect();
+            entries.reverse();
+            entries
+        } else {
+            entries.collect()
+        }
+    }
+
+    /// 按分数从小到大遍历
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.order.iter().map(|(score, member)| (member.as_str(), score.0))
+    }
+}
+
+impl FromIterator<(String, f64)> for SortedSet {
+    fn from_iter<I: IntoIterator<Item = (String, f64)>>(iter: I) -> Self {
+        let mut set = SortedSet::default();
+        for (member, score) in iter {
+            set.insert(member, score);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaderboard() -> SortedSet {
+        [("alice", 30.0), ("bob", 10.0), ("carol", 20.0), ("dave", 20.0)]
+            .into_iter()
+            .map(|(member, score)| (member.to_string(), score))
+            .collect()
+    }
+
+    #[test]
+    fn test_rank_and_ranges() {
+        let mut set = leaderboard();
+
+        assert_eq!(set.rank("bob", false), Some((0, 10.0)));
+        // 分数相同时按成员名排序
+        assert_eq!(set.rank("dave", false), Some((2, 20.0)));
+        assert_eq!(set.rank("alice", true), Some((0, 30.0)));
+        assert_eq!(set.rank("erin", false), None);
+
+        let members = |entries: Vec<(String, f64)>| entries.into_iter().map(|(m, _)| m).collect::<Vec<_>>();
+        assert_eq!(members(set.range_by_rank(0..=1, true)), ["alice", "dave"]);
+        assert_eq!(members(set.range_by_score(15.0, 30.0, false)), ["carol", "dave", "alice"]);
+        assert_eq!(members(set.range_by_score(15.0, 30.0, true)), ["alice", "dave", "carol"]);
+        assert!(set.range_by_score(30.0, 10.0, false).is_empty());
+
+        // 更新分数会调整排名
+        assert!(!set.insert("bob".to_string(), 40.0));
+        assert_eq!(set.rank("bob", true), Some((0, 40.0)));
+        assert_eq!(set.len(), 4);
+
+        assert!(set.remove("bob"));
+        assert!(!set.remove("bob"));
+        assert_eq!(set.rank("alice", true), Some((0, 30.0)));
+        assert_eq!(set.iter().count(), 3);
+    }
+}
@@ -0,0 +1,462 @@
+// src/write_behind.rs
+
+use crate::cache::{CacheValue, SnapshotValue};
+use crate::config::WriteBehindSettings;
+use crate::namespace::Namespaces;
+use log::{error, info, warn};
+use redb::{Database, TableDefinition};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// 待写入后端存储的一次变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Change {
+    Put {
+        ns: String,
+        key: String,
+        #[serde(flatten)]
+        value: SnapshotValue,
+    },
+    Delete { ns: String, key: String },
+}
+
+impl Change {
+    fn id(&self) -> (&str, &str) {
+        match self {
+            Change::Put { ns, key, .. } | Change::Delete { ns, key } => (ns, key),
+        }
+    }
+}
+
+/// 后端存储。`write_batch` 在阻塞线程中调用，一批变更要么全部生效要么全部失败。
+pub trait BackingStore: Send + Sync + 'static {
+    fn write_batch(&self, batch: &[Change]) -> io::Result<()>;
+}
+
+// (命名空间, 键) -> {"value": .., "content_type": .., "data_type": ..}
+const ENTRIES: TableDefinition<(&str, &str), &[u8]> = TableDefinition::new("entries");
+
+/// 基于 redb 嵌入式数据库的后端存储，每批变更在一个事务中提交
+pub struct RedbStore {
+    db: Database,
+}
+
+impl RedbStore {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let db = Database::create(path).map_err(io::Error::other)?;
+        Ok(Self { db })
+    }
+
+    /// 读取后端中保存的值
+    #[cfg(test)]
+    pub fn get(&self, namespace: &str, key: &str) -> io::Result<Option<CacheValue>> {
+        let txn = self.db.begin_read().map_err(io::Error::other)?;
+        let table = match txn.open_table(ENTRIES) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(e) => return Err(io::Error::other(e)),
+        };
+        let Some(bytes) = table.get((namespace, key)).map_err(io::Error::other)? else {
+            return Ok(None);
+        };
+        let stored: SnapshotValue = serde_json::from_slice(bytes.value())?;
+        Ok(CacheValue::from_snapshot(stored))
+    }
+}
+
+impl BackingStore for RedbStore {
+    fn write_batch(&self, batch: &[Change]) -> io::Result<()> {
+        let txn = self.db.begin_write().map_err(io::Error::other)?;
+        {
+            let mut table = txn.open_table(ENTRIES).map_err(io::Error::other)?;
+            for change in batch {
+                match change {
+                    Change::Put { ns, key, value } => {
+                        let bytes = serde_json::to_vec(value)?;
+                        table
+                            .insert((ns.as_str(), key.as_str()), bytes.as_slice())
+                            .map_err(io::Error::other)?;
+                    }
+                    Change::Delete { ns, key } => {
+                        table
+                            .remove((ns.as_str(), key.as_str()))
+                            .map_err(io::Error::other)?;
+                    }
+                }
+            }
+        }
+        txn.commit().map_err(io::Error::other)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    // 已入队但尚未写入或转入死信文件的变更数
+    queue_depth: AtomicU64,
+    // 成功写入的变更数（合并之后）
+    flushed: AtomicU64,
+    batches: AtomicU64,
+    retries: AtomicU64,
+    dead_lettered: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+/// 写回队列的统计信息
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteBehindStats {
+    pub queue_depth: u64,
+    pub flushed: u64,
+    pub batches: u64,
+    pub retries: u64,
+    pub dead_lettered: u64,
+    pub last_error: Option<String>,
+}
+
+/// 一个命名空间的写回队列句柄，由 `CacheStore` 在写入和删除时调用
+#[derive(Debug, Clone)]
+pub struct WriteBehindQueue {
+    namespace: String,
+    tx: UnboundedSender<Change>,
+    metrics: Arc<Metrics>,
+}
+
+impl WriteBehindQueue {
+    pub fn enqueue_put(&self, key: &str, value: &CacheValue) {
+        self.enqueue(Change::Put {
+            ns: self.namespace.clone(),
+            key: key.to_string(),
+            value: value.to_snapshot(),
+        });
+    }
+
+    pub fn enqueue_delete(&self, key: &str) {
+        self.enqueue(Change::Delete {
+            ns: self.namespace.clone(),
+            key: key.to_string(),
+        });
+    }
+
+    fn enqueue(&self, change: Change) {
+        self.metrics.queue_depth.fetch_add(1, Ordering::Relaxed);
+        if self.tx.send(change).is_err() {
+            self.metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            error!("Write-behind flusher has stopped, change not persisted");
+        }
+    }
+}
+
+/// 写回到后端存储。
+/// 写入只进入内存队列，由后台任务按批合并后写入；失败的批次按退避重试，
+/// 重试耗尽后写入死信文件。
+#[derive(Clone)]
+pub struct WriteBehind {
+    metrics: Arc<Metrics>,
+}
+
+impl WriteBehind {
+    /// 打开配置的 redb 数据库并开始写回
+    pub fn open(settings: &WriteBehindSettings, namespaces: &Namespaces) -> io::Result<Self> {
+        let store = RedbStore::open(&settings.path)?;
+        info!("Write-behind backing store opened at {}", settings.path);
+        Ok(Self::start(settings, Arc::new(store), namespaces))
+    }
+
+    /// 启动后台写回任务，随后把队列挂到所有命名空间上
+    pub fn start(
+        settings: &WriteBehindSettings,
+        store: Arc<dyn BackingStore>,
+        namespaces: &Namespaces,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let metrics = Arc::new(Metrics::default());
+        let flusher = Flusher {
+            store,
+            batch_size: settings.batch_size.max(1),
+            flush_interval: Duration::from_millis(settings.flush_interval_ms),
+            max_retries: settings.max_retries,
+            retry_backoff: Duration::from_millis(settings.retry_backoff_ms),
+            dead_letter_path: PathBuf::from(&settings.dead_letter_path),
+            metrics: Arc::clone(&metrics),
+        };
+        tokio::spawn(flusher.run(rx));
+
+        for (name, cache) in namespaces.iter() {
+            cache.attach_write_behind(WriteBehindQueue {
+                namespace: name.clone(),
+                tx: tx.clone(),
+                metrics: Arc::clone(&metrics),
+            });
+        }
+        Self { metrics }
+    }
+
+    pub fn stats(&self) -> WriteBehindStats {
+        let m = &self.metrics;
+        WriteBehindStats {
+            queue_depth: m.queue_depth.load(Ordering::Relaxed),
+            flushed: m.flushed.load(Ordering::Relaxed),
+            batches: m.batches.load(Ordering::Relaxed),
+            retries: m.retries.load(Ordering::Relaxed),
+            dead_lettered: m.dead_lettered.load(Ordering::Relaxed),
+            last_error: m.last_error.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// 死信文件中的一行
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    failed_at_ms: u64,
+    error: &'a str,
+    #[serde(flatten)]
+    change: &'a Change,
+}
+
+struct Flusher {
+    store: Arc<dyn BackingStore>,
+    batch_size: usize,
+    flush_interval: Duration,
+    max_retries: u32,
+    retry_backoff: Duration,
+    dead_letter_path: PathBuf,
+    metrics: Arc<Metrics>,
+}
+
+impl Flusher {
+    async fn run(self, mut rx: UnboundedReceiver<Change>) {
+        while let Some(first) = rx.recv().await {
+            // 收到第一条变更后最多等待一个刷写间隔，攒够一批或超时即写入
+            let mut batch = vec![first];
+            let deadline = tokio::time::Instant::now() + self.flush_interval;
+            while batch.len() < self.batch_size {
+                match tokio::time::timeout_at(deadline, rx.recv()).await {
+                    Ok(Some(change)) => batch.push(change),
+                    _ => break,
+                }
+            }
+            let queued = batch.len() as u64;
+            self.flush(coalesce(batch)).await;
+            self.metrics.queue_depth.fetch_sub(queued, Ordering::Relaxed);
+        }
+    }
+
+    async fn flush(&self, batch: Vec<Change>) {
+        let batch = Arc::new(batch);
+        let mut attempt = 0;
+        loop {
+            let store = Arc::clone(&self.store);
+            let changes = Arc::clone(&batch);
+            let result = tokio::task::spawn_blocking(move || store.write_batch(&changes))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)));
+
+            let error = match result {
+                Ok(()) => {
+                    self.metrics.batches.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.flushed.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                    return;
+                }
+                Err(e) => e.to_string(),
+            };
+            *self.metrics.last_error.lock().unwrap() = Some(error.clone());
+
+            if attempt >= self.max_retries {
+                self.dead_letter(&batch, &error).await;
+                return;
+            }
+            attempt += 1;
+            self.metrics.retries.fetch_add(1, Ordering::Relaxed);
+            let backoff = self.retry_backoff * 2u32.pow(attempt.min(10) - 1);
+            warn!(
+                "Write-behind batch of {} changes failed (attempt {}): {}, retrying in {:?}",
+                batch.len(),
+                attempt,
+                error,
+                backoff
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    async fn dead_letter(&self, batch: &Arc<Vec<Change>>, error: &str) {
+        error!(
+            "Write-behind batch of {} changes failed after {} retries, moving to {}: {}",
+            batch.len(),
+            self.max_retries,
+            self.dead_letter_path.display(),
+            error
+        );
+        let path = self.dead_letter_path.clone();
+        let changes = Arc::clone(batch);
+        let error = error.to_string();
+        let result = tokio::task::spawn_blocking(move || -> io::Result<()> {
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let mut writer = BufWriter::new(file);
+            let failed_at_ms = now_ms();
+            for change in changes.iter() {
+                let line = DeadLetter { failed_at_ms, error: &error, change };
+                serde_json::to_writer(&mut writer, &line)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            writer.get_ref().sync_data()
+        })
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)));
+
+        match result {
+            Ok(()) => {
+                self.metrics.dead_lettered.fetch_add(batch.len() as u64, Ordering::Relaxed);
+            }
+            Err(e) => error!("Failed to write dead letters, {} changes lost: {}", batch.len(), e),
+        }
+    }
+}
+
+/// 同一个键在一批中只保留最后一次变更
+fn coalesce(batch: Vec<Change>) -> Vec<Change> {
+    let mut seen = HashSet::new();
+    let keep: Vec<bool> = batch.iter().rev().map(|change| seen.insert(change.id())).collect();
+    let mut keep = keep.into_iter().rev();
+    batch.into_iter().filter(|_| keep.next().unwrap_or(false)).collect()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheItemTTL;
+    use crate::config::{CacheSettings, Settings};
+    use serde_json::{Value, json};
+    use std::fs;
+
+    fn test_settings(name: &str) -> Settings {
+        let path = |ext: &str| {
+            let path = std::env::temp_dir()
+                .join(format!("my-cache-{}-{}.{}", name, std::process::id(), ext));
+            let _ = fs::remove_file(&path);
+            path.to_string_lossy().into_owned()
+        };
+        let mut settings = Settings::for_tests(CacheSettings::for_tests());
+        settings.write_behind = WriteBehindSettings {
+            enabled: true,
+            path: path("redb"),
+            batch_size: 100,
+            flush_interval_ms: 20,
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            dead_letter_path: path("dead.jsonl"),
+        };
+        settings
+    }
+
+    async fn drained(write_behind: &WriteBehind) -> WriteBehindStats {
+        for _ in 0..200 {
+            let stats = write_behind.stats();
+            if stats.queue_depth == 0 {
+                return stats;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("write-behind queue was not drained");
+    }
+
+    // 前 `failures` 次写入失败的后端
+    struct FlakyStore {
+        failures: AtomicU64,
+        written: Mutex<Vec<Change>>,
+    }
+
+    impl BackingStore for FlakyStore {
+        fn write_batch(&self, batch: &[Change]) -> io::Result<()> {
+            let remaining = self.failures.load(Ordering::SeqCst);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, Ordering::SeqCst);
+                return Err(io::Error::other("backend unavailable"));
+            }
+            self.written.lock().unwrap().extend_from_slice(batch);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_reach_backing_store() {
+        let settings = test_settings("write-behind");
+        let namespaces = Namespaces::new(&settings);
+        let store = Arc::new(RedbStore::open(&settings.write_behind.path).unwrap());
+        let write_behind = WriteBehind::start(&settings.write_behind, store.clone(), &namespaces);
+        let cache = namespaces.get("").unwrap();
+
+        for i in 0..5 {
+            cache.set("a".to_string(), json!(i), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        }
+        cache.set("b".to_string(), json!("x"), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        cache.delete("b").await.unwrap();
+        let avatar = CacheValue::Binary {
+            content_type: "image/png".to_string(),
+            data: vec![1u8, 2, 3].into(),
+        };
+        cache.set("c".to_string(), avatar.clone(), CacheItemTTL::Permanent, vec!["t".to_string()]).await.unwrap();
+
+        let stats = drained(&write_behind).await;
+        // 同一批中对 a 的五次写入合并为一次
+        assert_eq!(stats.flushed, 3);
+        assert_eq!(store.get("default", "a").unwrap(), Some(CacheValue::Json(json!(4))));
+        assert_eq!(store.get("default", "b").unwrap(), None);
+        assert_eq!(store.get("default", "c").unwrap(), Some(avatar.clone()));
+
+        // 标签失效只作用于缓存
+        assert_eq!(cache.invalidate_tag("t").await, 1);
+        drained(&write_behind).await;
+        assert_eq!(store.get("default", "c").unwrap(), Some(avatar));
+
+        let _ = fs::remove_file(&settings.write_behind.path);
+    }
+
+    #[tokio::test]
+    async fn test_retry_and_dead_letter() {
+        let settings = test_settings("dead-letter");
+        let namespaces = Namespaces::new(&settings);
+        let store = Arc::new(FlakyStore {
+            failures: AtomicU64::new(1),
+            written: Mutex::new(Vec::new()),
+        });
+        let write_behind = WriteBehind::start(&settings.write_behind, store.clone(), &namespaces);
+        let cache = namespaces.get("").unwrap();
+
+        // 第一次失败，重试成功
+        cache.set("a".to_string(), json!(1), CacheItemTTL::Permanent, vec![]).await.unwrap();
+        let stats = drained(&write_behind).await;
+        assert_eq!((stats.retries, stats.flushed, stats.dead_lettered), (1, 1, 0));
+        assert_eq!(store.written.lock().unwrap().len(), 1);
+
+        // 重试耗尽后写入死信文件
+        store.failures.store(u64::MAX, Ordering::SeqCst);
+        cache.delete("a").await.unwrap();
+        let stats = drained(&write_behind).await;
+        assert_eq!((stats.retries, stats.dead_lettered), (3, 1));
+        assert_eq!(stats.last_error.as_deref(), Some("backend unavailable"));
+
+        let dead = fs::read_to_string(&settings.write_behind.dead_letter_path).unwrap();
+        let line: Value = serde_json::from_str(dead.lines().next().unwrap()).unwrap();
+        assert_eq!(line["op"], "delete");
+        assert_eq!(line["key"], "a");
+        assert_eq!(line["error"], "backend unavailable");
+
+        let _ = fs::remove_file(&settings.write_behind.dead_letter_path);
+    }
+}